@@ -0,0 +1,63 @@
+//! Compares the whole-buffer literal search with matching line by line, as
+//! `search` and the file search did before.
+//!
+//! Run with `cargo bench -p minigrep --bench literal`.
+
+use std::hint::black_box;
+use std::io::BufReader;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use minigrep::{search, search_numbered, search_reader_with_context};
+
+const LINES: usize = 200_000;
+
+/// About 11 MB of text, with a match every `every` lines.
+fn corpus(every: usize) -> String {
+    let mut contents = String::new();
+    for line in 0..LINES {
+        if line % every == 0 {
+            contents.push_str("How public, like a frog\n");
+        } else {
+            contents.push_str("To tell your name the livelong day to an admiring bog\n");
+        }
+    }
+    contents
+}
+
+fn bench_in_memory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    for every in [10, 10_000] {
+        let contents = corpus(every);
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_with_input(BenchmarkId::new("lines + str::contains", every), &contents, |b, contents| {
+            b.iter(|| search_numbered(black_box(contents), |line| line.contains("frog")).len())
+        });
+        group.bench_with_input(BenchmarkId::new("whole buffer", every), &contents, |b, contents| {
+            b.iter(|| search(black_box("frog"), black_box(contents)).len())
+        });
+    }
+    group.finish();
+}
+
+fn bench_streaming(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search_reader_with_context");
+    for every in [10, 10_000] {
+        let contents = corpus(every);
+        let is_match = |line: &[u8]| memchr::memmem::find(line, b"frog").is_some();
+        group.throughput(Throughput::Bytes(contents.len() as u64));
+        group.bench_with_input(BenchmarkId::new("line by line", every), &contents, |b, contents| {
+            b.iter(|| search_reader_with_context(BufReader::new(contents.as_bytes()), is_match, 0, 0).count())
+        });
+        group.bench_with_input(BenchmarkId::new("skip_lines_without", every), &contents, |b, contents| {
+            b.iter(|| {
+                search_reader_with_context(BufReader::new(contents.as_bytes()), is_match, 0, 0)
+                    .skip_lines_without(b"frog")
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_in_memory, bench_streaming);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Compares searching a directory serially (`-j1`) with the worker pool.
+//!
+//! Run with `cargo bench -p minigrep --bench parallel`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const FILES: usize = 200;
+const LINES_PER_FILE: usize = 2_000;
+
+/// Writes a tree of text files with a match every hundred lines.
+fn corpus() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("minigrep-bench-{}", std::process::id()));
+    for file in 0..FILES {
+        let dir = root.join(format!("dir{}", file % 10));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut contents = String::new();
+        for line in 0..LINES_PER_FILE {
+            if line % 100 == 0 {
+                contents.push_str("How public, like a frog\n");
+            } else {
+                contents.push_str("To tell your name the livelong day to an admiring bog\n");
+            }
+        }
+        fs::write(dir.join(format!("file{file}.txt")), contents).unwrap();
+    }
+    root
+}
+
+fn minigrep(threads: &str, root: &Path) {
+    let status = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args([threads, "frog"])
+        .arg(root)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn bench_directory_search(c: &mut Criterion) {
+    let root = corpus();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut group = c.benchmark_group("directory search");
+    group.bench_function("serial (-j1)", |b| b.iter(|| minigrep("-j1", &root)));
+    group.bench_function(format!("parallel (-j{threads})"), |b| {
+        b.iter(|| minigrep(&format!("-j{threads}"), &root))
+    });
+    group.finish();
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+criterion_group!(benches, bench_directory_search);
+criterion_main!(benches);
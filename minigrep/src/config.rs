@@ -0,0 +1,772 @@
+use std::env;
+
+use crate::error::Error;
+use crate::matcher::{Anchor, Syntax};
+use crate::settings;
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] QUERY [FILE]
+       minigrep [OPTIONS] (-e QUERY | -f QUERY_FILE)... [FILE]
+       minigrep index build [DIRECTORY OPTIONS] [DIR]
+       minigrep index query [OPTIONS] QUERY [DIR]
+
+Search FILE (or every file below a directory) for lines containing QUERY.
+With no FILE, or when FILE is -, read standard input.
+
+'index build' writes a trigram index of the files below DIR (default: .) to
+DIR/.minigrep-index. 'index query' searches only the indexed files that can
+contain QUERY, and files changed since the index was built. Files added since
+are not searched until the index is built again. Build with -z to index the
+text of compressed files. Use -e index to search for the word index.
+
+Options:
+  -i, --ignore-case     match without regard to case
+      --no-ignore-case  match case exactly, even if IGNORE_CASE is set
+  -n, --line-number     prefix each line with its line number
+  -v, --invert-match    select lines that do not match
+  -w, --word-regexp     match QUERY only as a whole word
+  -x, --line-regexp     match QUERY only against the whole line
+  -c, --count           print only the number of selected lines
+  -l, --files-with-matches  print only the names of files with a selected line
+  -L, --files-without-match print only the names of files without one
+  -m, --max-count NUM   stop reading a file after NUM selected lines
+  -q, --quiet           print nothing, and stop at the first selected line; the
+                        exit status tells whether there was one
+  -A, --after-context NUM   print NUM lines after each match
+  -B, --before-context NUM  print NUM lines before each match
+  -C, --context NUM         print NUM lines before and after each match
+      --regex           treat QUERY as a regular expression
+      --fuzzy NUM       also match text within NUM inserted, deleted or changed
+                        characters of QUERY; the lines of each file are printed
+                        closest first, unless printed with context or -v
+  -e, --regexp QUERY    search for QUERY; may be repeated to match any of them
+  -f, --file QUERY_FILE search for every line of QUERY_FILE
+      --all             select lines matching every query instead of any
+      --color[=WHEN]    highlight matches: auto (the default), always or never
+      --json            print one JSON object per line, then a summary object
+  -a, --text            search binary files as if they were text
+  -z, --search-zip      search the decompressed text of gzip, zstd and xz files,
+                        recognized by their first bytes; line numbers count
+                        decompressed lines
+  -j, --threads NUM     search NUM files at a time (default: one per CPU)
+      --mmap            map files into memory instead of reading them (the
+                        default for files of 16 MiB or more)
+      --no-mmap         always read files, never map them
+      --replace TEXT    print matching lines with every match replaced by TEXT;
+                        with --regex and one QUERY, $1 or $name in TEXT stand
+                        for the groups it captured
+      --write           with --replace, rewrite the files in place and print how
+                        many replacements were made in each
+      --watch           keep running: print lines matching as they are appended
+                        to FILE (or any file below it), and search a file again
+                        when it is rewritten
+      --tui             browse the selected lines in the terminal, with the
+                        lines around the selected one previewed: type to edit
+                        QUERY, Up/Down to select, Enter to open the line in
+                        $EDITOR, Esc to quit
+  -h, --help            print this help and exit
+  -V, --version         print version information and exit
+
+Directory options:
+      --include GLOB    only search files matching GLOB; may be repeated
+      --exclude GLOB    skip files and directories matching GLOB; may be repeated
+      --hidden          also search hidden files and directories
+      --no-ignore       also search files listed in .gitignore and .ignore files
+
+  Globs use .gitignore syntax, relative to the directory being searched.
+  Binary files found in a directory are skipped; a binary FILE given on the
+  command line is only reported as matching. -a turns both off.
+
+Environment:
+  IGNORE_CASE           ignore case unless --no-ignore-case is given
+  NO_COLOR              when set and not empty, --color=auto never colors, and
+                        neither does a color set in a configuration file
+
+Exit status:
+  0 when a line was selected (or replaced, with --write), 1 when none was, and 2
+  on errors, also when some files of a tree could not be searched.
+
+Configuration:
+      --no-config       ignore the configuration files below
+
+  Defaults for options are read from ~/.config/minigrep/config.toml (or
+  $XDG_CONFIG_HOME/minigrep/config.toml), then from the nearest .minigreprc in
+  the current directory or above it. Both are TOML, for example:
+
+      color = \"always\"
+      ignore-case = true
+      line-number = true
+      context = 2
+      exclude = [\"target\", \"*.min.js\"]
+
+  Other settings are before-context, after-context, include, hidden, no-ignore,
+  text, search-zip, threads and mmap. The command line overrides the environment,
+  which overrides .minigreprc, which overrides config.toml. Globs to include or
+  exclude are added up from every layer instead.";
+
+pub struct Config {
+    /// Queries given on the command line, either positionally or with -e.
+    pub patterns: Vec<String>,
+    /// Files whose lines are further queries, read when the search runs.
+    pub pattern_files: Vec<String>,
+    pub all_patterns: bool,
+    pub file_path: String,
+    pub ignore_case : bool,
+    pub regex: bool,
+    /// The edit distance allowed by `--fuzzy`, if given.
+    pub fuzzy: Option<usize>,
+    pub line_number: bool,
+    pub invert_match: bool,
+    /// Only match whole words (-w); ignored when `line_regexp` is set.
+    pub word_regexp: bool,
+    /// Only match whole lines (-x).
+    pub line_regexp: bool,
+    pub count: bool,
+    /// Print only the names of the files with a selected line (-l).
+    pub files_with_matches: bool,
+    /// Print only the names of the files without a selected line (-L).
+    pub files_without_match: bool,
+    /// Stop reading a file after this many selected lines (-m).
+    pub max_count: Option<usize>,
+    /// Print nothing; only the exit status matters (-q).
+    pub quiet: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub color: ColorChoice,
+    pub json: bool,
+    /// How many files of a directory to search at once; `None` means one per CPU.
+    pub threads: Option<usize>,
+    /// Whether to map files into memory (`--mmap`) or read them (`--no-mmap`);
+    /// `None` leaves it to the size of each file.
+    pub mmap: Option<bool>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    /// Treat binary input (containing NUL bytes) as text.
+    pub text: bool,
+    /// Decompress gzip, zstd and xz input before searching it (-z).
+    pub search_zip: bool,
+    /// Text that replaces every match in the lines printed.
+    pub replace: Option<String>,
+    /// Rewrite the files searched with `replace` applied instead of printing them.
+    pub write: bool,
+    /// Search only the files of `file_path` its index says can match (`index query`).
+    pub indexed: bool,
+    /// Keep searching what is added to the files, see `--watch`.
+    pub watch: bool,
+    /// Browse the results in a terminal UI instead of printing them (`--tui`).
+    pub tui: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            patterns: Vec::new(),
+            pattern_files: Vec::new(),
+            all_patterns: false,
+            file_path: String::new(),
+            ignore_case: false,
+            regex: false,
+            fuzzy: None,
+            line_number: false,
+            invert_match: false,
+            word_regexp: false,
+            line_regexp: false,
+            count: false,
+            files_with_matches: false,
+            files_without_match: false,
+            max_count: None,
+            quiet: false,
+            before_context: 0,
+            after_context: 0,
+            color: ColorChoice::Auto,
+            json: false,
+            threads: None,
+            mmap: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            hidden: false,
+            no_ignore: false,
+            text: false,
+            search_zip: false,
+            replace: None,
+            write: false,
+            indexed: false,
+            watch: false,
+            tui: false,
+        }
+    }
+}
+
+impl Config {
+    /// How the queries are to be understood, as chosen by --regex and --fuzzy.
+    pub fn syntax(&self) -> Syntax {
+        match (self.regex, self.fuzzy) {
+            (true, _) => Syntax::Regex,
+            (false, Some(max)) => Syntax::Fuzzy(max),
+            (false, None) => Syntax::Literal,
+        }
+    }
+
+    /// Where in a line the queries may match, as chosen by -w and -x.
+    pub fn anchor(&self) -> Anchor {
+        match (self.line_regexp, self.word_regexp) {
+            (true, _) => Anchor::Line,
+            (false, true) => Anchor::Word,
+            (false, false) => Anchor::Anywhere,
+        }
+    }
+}
+
+/// When to color the output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    /// Only when standard output is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+/// What the command line asked `minigrep` to do.
+// Built once per run, so the size of `Config` does not matter.
+#[allow(clippy::large_enum_variant)]
+pub enum Command {
+    Search(Config),
+    /// Build the index of the directory in `file_path`.
+    BuildIndex(Config),
+    Help,
+    Version,
+}
+
+impl Command {
+    /// Parses `args` (including the program name) into a [`Command`].
+    ///
+    /// Flags may appear anywhere before `--`; after it every argument is
+    /// positional. Short flags can be grouped, as in `-in`.
+    ///
+    /// Options start out as set by the configuration files, then the
+    /// environment, each layer overriding the one before, and the command
+    /// line has the last word. `--no-config` skips the files, and so do
+    /// `--help` and `--version`, which a broken file must not get in the way of.
+    pub fn parse(args: &[String]) -> Result<Command, Error> {
+        if let Ok(command @ (Command::Help | Command::Version)) = parse_args(args, Config::default()) {
+            return Ok(command);
+        }
+        let mut defaults = Config::default();
+        if !args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--no-config") {
+            for path in [settings::user_file(), settings::project_file()].into_iter().flatten() {
+                settings::apply_file(&mut defaults, &path)?;
+            }
+        }
+        if env::var("IGNORE_CASE").is_ok() {
+            defaults.ignore_case = true;
+        }
+        if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            defaults.color = ColorChoice::Auto;
+        }
+        parse_args(args, defaults).map_err(Error::Usage)
+    }
+}
+
+/// Parses `args` on top of the options already set in `defaults`.
+fn parse_args(args: &[String], defaults: Config) -> Result<Command, String> {
+    // For `index build` and `index query` the word `index` stands in for the
+    // program name, which is skipped below, and the rest parses as usual.
+    let (subcommand, args) = match args.get(1..3) {
+        Some([index, subcommand]) if index == "index" && (subcommand == "build" || subcommand == "query") => {
+            (Some(subcommand.as_str()), &args[2..])
+        }
+        _ => (None, args),
+    };
+
+    let mut config = defaults;
+    let mut positional = Vec::new();
+    let mut only_positional = false;
+    // -A and -B win over -C whatever order they are given in, as in grep.
+    let (mut before, mut after, mut context) = (None, None, None);
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if only_positional || arg == "-" || !arg.starts_with('-') {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (long, inline_value) = match long.split_once('=') {
+                Some((long, value)) => (long, Some(value.to_string())),
+                None => (long, None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next().cloned());
+
+            match long {
+                "" => only_positional = true,
+                "ignore-case" => config.ignore_case = true,
+                "no-ignore-case" => config.ignore_case = false,
+                "line-number" => config.line_number = true,
+                "invert-match" => config.invert_match = true,
+                "word-regexp" => config.word_regexp = true,
+                "line-regexp" => config.line_regexp = true,
+                "count" => config.count = true,
+                "files-with-matches" => config.files_with_matches = true,
+                "files-without-match" => config.files_without_match = true,
+                "max-count" => config.max_count = Some(max_count(long, value())?),
+                "quiet" | "silent" => config.quiet = true,
+                "regex" => config.regex = true,
+                "fuzzy" => config.fuzzy = Some(edit_distance(long, value())?),
+                "regexp" => config.patterns.push(required(long, value())?),
+                "file" => config.pattern_files.push(required(long, value())?),
+                "all" => config.all_patterns = true,
+                "after-context" => after = Some(context_length(long, value())?),
+                "before-context" => before = Some(context_length(long, value())?),
+                "context" => context = Some(context_length(long, value())?),
+                "color" | "colour" => {
+                    config.color = match inline_value.as_deref() {
+                        None => ColorChoice::Auto,
+                        Some(when) => color_choice(when)?,
+                    }
+                }
+                "json" => config.json = true,
+                "threads" => config.threads = Some(thread_count(long, value())?),
+                "mmap" => config.mmap = Some(true),
+                "no-mmap" => config.mmap = Some(false),
+                "include" => config.include.push(required(long, value())?),
+                "exclude" => config.exclude.push(required(long, value())?),
+                "hidden" => config.hidden = true,
+                "no-ignore" => config.no_ignore = true,
+                "text" => config.text = true,
+                "search-zip" => config.search_zip = true,
+                "replace" => config.replace = Some(required(long, value())?),
+                "write" => config.write = true,
+                "watch" => config.watch = true,
+                "tui" => config.tui = true,
+                // Already taken into account by `Command::parse`.
+                "no-config" => {}
+                "help" => return Ok(Command::Help),
+                "version" => return Ok(Command::Version),
+                _ => return Err(format!("unrecognized option '{arg}'")),
+            }
+            continue;
+        }
+
+        for (index, flag) in arg.char_indices().skip(1) {
+            // An option taking a value uses the rest of the group, or else the next argument.
+            let mut value = || {
+                let rest = &arg[index + flag.len_utf8()..];
+                if rest.is_empty() { args.next().cloned() } else { Some(rest.to_string()) }
+            };
+
+            match flag {
+                'i' => config.ignore_case = true,
+                'n' => config.line_number = true,
+                'v' => config.invert_match = true,
+                'w' => config.word_regexp = true,
+                'x' => config.line_regexp = true,
+                'c' => config.count = true,
+                'l' => config.files_with_matches = true,
+                'L' => config.files_without_match = true,
+                'm' => config.max_count = Some(max_count("max-count", value())?),
+                'q' => config.quiet = true,
+                'a' => config.text = true,
+                'z' => config.search_zip = true,
+                'e' => config.patterns.push(required("regexp", value())?),
+                'f' => config.pattern_files.push(required("file", value())?),
+                'j' => config.threads = Some(thread_count("threads", value())?),
+                'A' => after = Some(context_length("after-context", value())?),
+                'B' => before = Some(context_length("before-context", value())?),
+                'C' => context = Some(context_length("context", value())?),
+                'h' => return Ok(Command::Help),
+                'V' => return Ok(Command::Version),
+                _ => return Err(format!("invalid option -- '{flag}'")),
+            }
+            if SHORT_WITH_VALUE.contains(flag) {
+                break;
+            }
+        }
+    }
+
+    config.before_context = before.or(context).unwrap_or(config.before_context);
+    config.after_context = after.or(context).unwrap_or(config.after_context);
+
+    let mut positional = positional.into_iter();
+    let building = subcommand == Some("build");
+    // With -e or -f the queries are given, so the first positional is a FILE.
+    if !building && config.patterns.is_empty() && config.pattern_files.is_empty() {
+        config.patterns.push(positional.next().ok_or("missing QUERY")?);
+    }
+    // An index is always of a directory, the current one unless given.
+    let default_path = if subcommand.is_some() { "." } else { "-" };
+    config.file_path = positional.next().unwrap_or_else(|| default_path.to_string());
+    config.indexed = subcommand == Some("query");
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument '{extra}'"));
+    }
+    if building {
+        return Ok(Command::BuildIndex(config));
+    }
+    if config.regex && config.fuzzy.is_some() {
+        return Err("options '--regex' and '--fuzzy' cannot be used together".to_string());
+    }
+    if config.files_with_matches && config.files_without_match {
+        return Err("options '--files-with-matches' and '--files-without-match' cannot be used together".to_string());
+    }
+    if config.json && (config.files_with_matches || config.files_without_match) {
+        return Err("option '--json' cannot list file names".to_string());
+    }
+    // Records are of lines as they are; with --write they say what was rewritten.
+    if config.json && config.count {
+        return Err("option '--json' cannot count lines".to_string());
+    }
+    if config.json && config.replace.is_some() && !config.write {
+        return Err("option '--json' cannot print replaced lines".to_string());
+    }
+    if config.write && config.replace.is_none() {
+        return Err("option '--write' requires '--replace'".to_string());
+    }
+    // Lines selected by -v contain no match to replace.
+    if config.replace.is_some() && config.invert_match {
+        return Err("options '--replace' and '--invert-match' cannot be used together".to_string());
+    }
+    if config.write && (config.files_with_matches || config.files_without_match || config.quiet) {
+        return Err("option '--write' cannot be used with -l, -L or -q".to_string());
+    }
+    if config.write && config.search_zip {
+        return Err("option '--write' cannot rewrite compressed files".to_string());
+    }
+    if config.write && config.file_path == "-" {
+        return Err("option '--write' cannot rewrite standard input".to_string());
+    }
+    if config.watch {
+        if config.file_path == "-" {
+            return Err("option '--watch' cannot watch standard input".to_string());
+        }
+        // These summarize files, stop reading them early, or change them,
+        // which only makes sense once; compressed files cannot be read from
+        // where the last poll stopped.
+        let conflicting = [
+            ("count", config.count),
+            ("json", config.json),
+            ("write", config.write),
+            ("search-zip", config.search_zip),
+            ("files-with-matches", config.files_with_matches),
+            ("files-without-match", config.files_without_match),
+            ("max-count", config.max_count.is_some()),
+            ("quiet", config.quiet),
+        ];
+        if let Some((option, _)) = conflicting.iter().find(|(_, given)| *given) {
+            return Err(format!("options '--watch' and '--{option}' cannot be used together"));
+        }
+    }
+
+    if config.tui {
+        // The query is edited in the browser, and the input searched again.
+        if config.patterns.len() != 1 || !config.pattern_files.is_empty() {
+            return Err("option '--tui' needs a single QUERY".to_string());
+        }
+        if config.file_path == "-" {
+            return Err("option '--tui' cannot browse standard input".to_string());
+        }
+        let conflicting = [
+            ("count", config.count),
+            ("json", config.json),
+            ("replace", config.replace.is_some()),
+            ("watch", config.watch),
+            ("files-with-matches", config.files_with_matches),
+            ("files-without-match", config.files_without_match),
+            ("quiet", config.quiet),
+        ];
+        if let Some((option, _)) = conflicting.iter().find(|(_, given)| *given) {
+            return Err(format!("options '--tui' and '--{option}' cannot be used together"));
+        }
+    }
+
+    Ok(Command::Search(config))
+}
+
+/// Short options that take a value, either glued on (`-A3`) or as the next argument.
+const SHORT_WITH_VALUE: &str = "ABCefjm";
+
+pub(crate) fn color_choice(when: &str) -> Result<ColorChoice, String> {
+    match when {
+        "auto" => Ok(ColorChoice::Auto),
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        _ => Err(format!("invalid argument '{when}' for '--color'")),
+    }
+}
+
+fn required(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("option '--{option}' requires an argument"))
+}
+
+fn context_length(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = required(option, value)?;
+    value.parse().map_err(|_| format!("invalid context length argument '{value}'"))
+}
+
+fn max_count(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = required(option, value)?;
+    value.parse().map_err(|_| format!("invalid max count '{value}'"))
+}
+
+fn edit_distance(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = required(option, value)?;
+    value.parse().map_err(|_| format!("invalid edit distance '{value}'"))
+}
+
+fn thread_count(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = required(option, value)?;
+    match value.parse() {
+        Ok(0) | Err(_) => Err(format!("invalid thread count '{value}'")),
+        Ok(threads) => Ok(threads),
+    }
+}
+
+/// Parses `args` the way `Command::parse` would on a machine without
+/// configuration files or `IGNORE_CASE`, for the tests of other modules.
+#[cfg(test)]
+pub(crate) fn test_config(args: &[&str]) -> Config {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    match parse_args(&args, Config::default()) {
+        Ok(Command::Search(config) | Command::BuildIndex(config)) => config,
+        _ => panic!("expected a search or an index build for {args:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], ignore_case: bool) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_args(&args, Config { ignore_case, ..Config::default() })
+    }
+
+    fn config(args: &[&str], ignore_case: bool) -> Config {
+        match parse(args, ignore_case) {
+            Ok(Command::Search(config)) => config,
+            _ => panic!("expected a search command for {args:?}"),
+        }
+    }
+
+    #[test]
+    fn positional_query_and_file() {
+        let config = config(&["minigrep", "frog", "poem.txt"], false);
+
+        assert_eq!(vec!["frog"], config.patterns);
+        assert_eq!("poem.txt", config.file_path);
+        assert!(!config.ignore_case && !config.regex && !config.count);
+    }
+
+    #[test]
+    fn grouped_and_long_flags() {
+        let config = config(&["minigrep", "-nv", "frog", "--count", "poem.txt", "--regex"], false);
+
+        assert!(config.line_number && config.invert_match && config.count && config.regex);
+    }
+
+    #[test]
+    fn word_and_line_flags() {
+        let short = config(&["minigrep", "-wx", "frog"], false);
+        let long = config(&["minigrep", "--word-regexp", "frog"], false);
+
+        assert!(short.word_regexp && short.line_regexp);
+        assert!(long.word_regexp && !long.line_regexp);
+    }
+
+    #[test]
+    fn replace_and_write() {
+        let config = config(&["minigrep", "--replace", "toad", "--write", "frog", "poem.txt"], false);
+
+        assert_eq!(Some("toad".to_string()), config.replace);
+        assert!(config.write);
+        assert!(parse(&["minigrep", "--write", "frog", "poem.txt"], false).is_err());
+        assert!(parse(&["minigrep", "--replace=toad", "--write", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "-v", "--replace=toad", "frog", "poem.txt"], false).is_err());
+        assert!(parse(&["minigrep", "-v", "--replace=toad", "--write", "frog", "poem.txt"], false).is_err());
+    }
+
+    #[test]
+    fn fuzzy_distance() {
+        assert_eq!(Some(2), config(&["minigrep", "--fuzzy", "2", "frog"], false).fuzzy);
+        assert_eq!(Syntax::Fuzzy(1), config(&["minigrep", "--fuzzy=1", "frog"], false).syntax());
+        assert!(parse(&["minigrep", "--fuzzy=x", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "--fuzzy=1", "--regex", "frog"], false).is_err());
+    }
+
+    #[test]
+    fn index_subcommands() {
+        let Ok(Command::BuildIndex(build)) = parse(&["minigrep", "index", "build", "--hidden"], false) else {
+            panic!("expected an index build")
+        };
+        let query = config(&["minigrep", "index", "query", "-i", "frog"], false);
+        let search = config(&["minigrep", "index", "poem.txt"], false);
+
+        assert!(build.hidden && build.patterns.is_empty());
+        assert_eq!(".", build.file_path);
+        assert!(query.indexed && query.ignore_case);
+        assert_eq!((vec!["frog".to_string()], "."), (query.patterns, query.file_path.as_str()));
+        assert!(!search.indexed);
+        assert_eq!((vec!["index".to_string()], "poem.txt"), (search.patterns, search.file_path.as_str()));
+    }
+
+    #[test]
+    fn watch_needs_files_and_line_output() {
+        assert!(config(&["minigrep", "--watch", "frog", "poem.txt"], false).watch);
+        assert!(parse(&["minigrep", "--watch", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "--watch", "-c", "frog", "poem.txt"], false).is_err());
+        assert!(parse(&["minigrep", "--watch", "--json", "frog", "poem.txt"], false).is_err());
+        assert!(parse(&["minigrep", "--watch", "-z", "frog", "poem.txt"], false).is_err());
+    }
+
+    #[test]
+    fn search_zip() {
+        assert!(config(&["minigrep", "-zn", "frog", "poem.txt.gz"], false).search_zip);
+        assert!(config(&["minigrep", "--search-zip", "frog", "poem.txt.gz"], false).search_zip);
+        assert!(!config(&["minigrep", "frog", "poem.txt.gz"], false).search_zip);
+        assert!(parse(&["minigrep", "-z", "--replace=toad", "--write", "frog", "poem.txt.gz"], false).is_err());
+    }
+
+    #[test]
+    fn tui_needs_one_query_and_files() {
+        assert!(config(&["minigrep", "--tui", "frog", "."], false).tui);
+        assert!(parse(&["minigrep", "--tui", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "--tui", "-e", "frog", "-e", "bog", "."], false).is_err());
+        assert!(parse(&["minigrep", "--tui", "-c", "frog", "."], false).is_err());
+    }
+
+    #[test]
+    fn summary_modes() {
+        let listed = config(&["minigrep", "-lqm2", "frog", "poem.txt"], false);
+        assert!(listed.files_with_matches && listed.quiet && !listed.files_without_match);
+        assert_eq!(Some(2), listed.max_count);
+        assert_eq!(Some(0), config(&["minigrep", "--max-count=0", "-L", "frog"], false).max_count);
+
+        assert!(parse(&["minigrep", "-m", "-1", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "-l", "-L", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "-l", "--json", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "-c", "--json", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "--replace=toad", "--json", "frog"], false).is_err());
+        assert!(parse(&["minigrep", "--replace=toad", "--write", "--json", "frog", "poem.txt"], false).is_ok());
+        assert!(parse(&["minigrep", "-q", "--replace=toad", "--write", "frog", "poem.txt"], false).is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_defaults() {
+        let args: Vec<String> = ["minigrep", "--color=never", "-A1", "--exclude=*.log", "--no-config", "frog"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let defaults = Config {
+            color: ColorChoice::Always,
+            before_context: 2,
+            after_context: 2,
+            exclude: vec!["target".to_string()],
+            line_number: true,
+            ..Config::default()
+        };
+        let Ok(Command::Search(config)) = parse_args(&args, defaults) else { panic!("not a search") };
+
+        assert_eq!(ColorChoice::Never, config.color);
+        assert_eq!((2, 1), (config.before_context, config.after_context));
+        assert_eq!(vec!["target", "*.log"], config.exclude);
+        assert!(config.line_number);
+    }
+
+    #[test]
+    fn flags_override_environment() {
+        assert!(config(&["minigrep", "-i", "frog", "poem.txt"], false).ignore_case);
+        assert!(config(&["minigrep", "frog", "poem.txt"], true).ignore_case);
+        assert!(!config(&["minigrep", "--no-ignore-case", "frog", "poem.txt"], true).ignore_case);
+    }
+
+    #[test]
+    fn repeated_and_file_patterns() {
+        let args = ["minigrep", "-e", "frog", "--regexp=bog", "-fqueries.txt", "--all", "poem.txt"];
+        let config = config(&args, false);
+
+        assert_eq!(vec!["frog", "bog"], config.patterns);
+        assert_eq!(vec!["queries.txt"], config.pattern_files);
+        assert_eq!("poem.txt", config.file_path);
+        assert!(config.all_patterns);
+    }
+
+    #[test]
+    fn file_defaults_to_stdin() {
+        assert_eq!("-", config(&["minigrep", "frog"], false).file_path);
+        assert_eq!("-", config(&["minigrep", "frog", "-"], false).file_path);
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = config(&["minigrep", "--", "-v", "poem.txt"], false);
+
+        assert_eq!(vec!["-v"], config.patterns);
+        assert!(!config.invert_match);
+    }
+
+    #[test]
+    fn context_options() {
+        let grouped = config(&["minigrep", "-nA2", "frog", "-B", "1"], false);
+        assert_eq!((1, 2), (grouped.before_context, grouped.after_context));
+        assert!(grouped.line_number);
+
+        let long = config(&["minigrep", "--after-context=1", "--context", "3", "frog"], false);
+        assert_eq!((3, 1), (long.before_context, long.after_context));
+    }
+
+    #[test]
+    fn color_option() {
+        assert_eq!(ColorChoice::Auto, config(&["minigrep", "frog"], false).color);
+        assert_eq!(ColorChoice::Auto, config(&["minigrep", "--color", "frog"], false).color);
+        assert_eq!(ColorChoice::Always, config(&["minigrep", "--color=always", "frog"], false).color);
+        assert_eq!(ColorChoice::Never, config(&["minigrep", "--colour=never", "frog"], false).color);
+        assert_eq!(
+            Some("invalid argument 'red' for '--color'".to_string()),
+            parse(&["minigrep", "--color=red", "frog"], false).err()
+        );
+    }
+
+    #[test]
+    fn thread_count_option() {
+        assert_eq!(None, config(&["minigrep", "frog"], false).threads);
+        assert_eq!(Some(4), config(&["minigrep", "-j4", "frog"], false).threads);
+        assert_eq!(Some(1), config(&["minigrep", "--threads", "1", "frog"], false).threads);
+        assert_eq!(
+            Some("invalid thread count '0'".to_string()),
+            parse(&["minigrep", "-j", "0", "frog"], false).err()
+        );
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(parse(&["minigrep", "frog", "--help"], false), Ok(Command::Help)));
+        assert!(matches!(parse(&["minigrep", "-V"], false), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(Some("missing QUERY".to_string()), parse(&["minigrep"], false).err());
+        assert_eq!(
+            Some("unrecognized option '--frog'".to_string()),
+            parse(&["minigrep", "--frog", "a", "b"], false).err()
+        );
+        assert_eq!(
+            Some("invalid option -- 'k'".to_string()),
+            parse(&["minigrep", "-nk", "a", "b"], false).err()
+        );
+        assert_eq!(
+            Some("unexpected argument 'c'".to_string()),
+            parse(&["minigrep", "a", "b", "c"], false).err()
+        );
+        assert_eq!(
+            Some("invalid context length argument 'x'".to_string()),
+            parse(&["minigrep", "-Cx", "a"], false).err()
+        );
+        assert_eq!(
+            Some("option '--after-context' requires an argument".to_string()),
+            parse(&["minigrep", "a", "-A"], false).err()
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{self, BufRead, BufReader};
+
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+/// The compression formats `-z` can read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+/// The bytes each format starts with.
+const MAGIC: [(Format, &[u8]); 3] = [
+    (Format::Gzip, b"\x1f\x8b"),
+    (Format::Zstd, b"\x28\xb5\x2f\xfd"),
+    (Format::Xz, b"\xfd7zXZ\0"),
+];
+
+/// Looks at the start of `reader`, without consuming it, for the magic bytes
+/// of a compression format. File names are not looked at, so rotated logs
+/// such as `app.log.1` are recognized too.
+fn detect(reader: &mut impl BufRead) -> io::Result<Option<Format>> {
+    let start = reader.fill_buf()?;
+    Ok(MAGIC.iter().find(|(_, magic)| start.starts_with(magic)).map(|&(format, _)| format))
+}
+
+/// Reads what `reader` decompresses to. Concatenated gzip members and xz
+/// streams are read one after the other, as `zcat` and `xzcat` do.
+fn decoder<'a>(format: Format, reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match format {
+        Format::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Format::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Format::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+    })
+}
+
+/// Reads `reader` through a decoder if it is compressed, or as it is if not.
+pub(crate) fn decompressed<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    match detect(&mut reader)? {
+        Some(format) => decoder(format, reader),
+        None => Ok(Box::new(reader)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn compress(format: Format, data: &[u8]) -> Vec<u8> {
+        match format {
+            Format::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Format::Zstd => zstd::encode_all(data, 0).unwrap(),
+            Format::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn detects_and_decompresses_every_format() {
+        for format in [Format::Gzip, Format::Zstd, Format::Xz] {
+            let compressed = compress(format, b"a frog\nbog\n");
+            assert_eq!(Some(format), detect(&mut &compressed[..]).unwrap());
+
+            let mut text = String::new();
+            decompressed(&compressed[..]).unwrap().read_to_string(&mut text).unwrap();
+            assert_eq!("a frog\nbog\n", text, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn plain_input_is_read_as_it_is() {
+        let mut text = String::new();
+        decompressed(&b"a frog\n"[..]).unwrap().read_to_string(&mut text).unwrap();
+
+        assert_eq!(None, detect(&mut &b"a frog\n"[..]).unwrap());
+        assert_eq!("a frog\n", text);
+    }
+
+    #[test]
+    fn concatenated_gzip_members() {
+        let mut compressed = compress(Format::Gzip, b"a frog\n");
+        compressed.extend(compress(Format::Gzip, b"a bog\n"));
+
+        let mut text = String::new();
+        decompressed(&compressed[..]).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!("a frog\na bog\n", text);
+    }
+}
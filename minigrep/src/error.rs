@@ -0,0 +1,87 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything that can stop `minigrep` from doing what it was asked.
+#[derive(Debug)]
+pub enum Error {
+    /// The command line could not be understood, e.g. a missing QUERY or an
+    /// unknown option.
+    Usage(String),
+    /// A configuration file could not be read or understood.
+    Settings { path: PathBuf, message: String },
+    /// A query given with `--regex` is not a valid regular expression.
+    Regex { query: String, source: regex::Error },
+    /// A file (or standard input) could not be read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// `index query` was run on a directory without an index.
+    MissingIndex { path: PathBuf },
+    /// The results could not be written.
+    Output(io::Error),
+    /// The search went on past files it could not search, which were
+    /// reported as they came up.
+    Incomplete { skipped: usize },
+}
+
+impl Error {
+    /// Whether the output was closed, e.g. by `minigrep ... | head`.
+    pub(crate) fn is_broken_pipe(&self) -> bool {
+        match self {
+            Error::Io { source, .. } | Error::Output(source) => source.kind() == io::ErrorKind::BrokenPipe,
+            _ => false,
+        }
+    }
+}
+
+/// Why searching one input failed, keeping a failure to read it apart from a
+/// failure to write what was found in it.
+#[derive(Debug)]
+pub(crate) enum SearchError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+impl SearchError {
+    /// The [`Error`] for failing to search the input named `path`.
+    pub(crate) fn at(self, path: &Path) -> Error {
+        match self {
+            SearchError::Read(source) => Error::Io { path: path.to_path_buf(), source },
+            SearchError::Write(source) => Error::Output(source),
+        }
+    }
+}
+
+/// Printing is mostly writing, so `?` on an [`io::Error`] marks a failed
+/// write; reads are marked [`SearchError::Read`] where they happen.
+impl From<io::Error> for SearchError {
+    fn from(e: io::Error) -> Self {
+        SearchError::Write(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message) => f.write_str(message),
+            Error::Settings { path, message } => write!(f, "{}: {message}", path.display()),
+            Error::Regex { query, source } => write!(f, "invalid regular expression '{query}': {source}"),
+            Error::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::MissingIndex { path } => {
+                write!(f, "{}: no index, run 'minigrep index build' first", path.display())
+            }
+            Error::Output(source) => write!(f, "cannot write results: {source}"),
+            Error::Incomplete { skipped: 1 } => f.write_str("1 file could not be searched"),
+            Error::Incomplete { skipped } => write!(f, "{skipped} files could not be searched"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Regex { source, .. } => Some(source),
+            Error::Io { source, .. } | Error::Output(source) => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Take, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use bstr::ByteSlice;
+use tempfile::NamedTempFile;
+
+use crate::config::Config;
+use crate::decompress;
+use crate::matcher::{fold, Matcher};
+use crate::stream::is_binary;
+use crate::walk;
+
+/// The file an index is kept in, at the top of the directory it indexes.
+pub(crate) const INDEX_FILE: &str = ".minigrep-index";
+
+const MAGIC: &[u8] = b"minigrep-index 1\n";
+
+type Trigram = [u8; 3];
+
+/// A trigram index of the files below a directory: for every three bytes in
+/// a row of the case-folded text of a line, the files where they occur.
+///
+/// A file can only contain a literal query if it contains every trigram of
+/// the folded query, so the index rules out most files without reading them.
+struct Index {
+    files: Vec<IndexedFile>,
+    /// Indices into `files`, in increasing order.
+    postings: BTreeMap<Trigram, Vec<u32>>,
+}
+
+struct IndexedFile {
+    /// Relative to the indexed directory.
+    path: String,
+    /// Size and modification time when indexed, to notice later changes.
+    size: u64,
+    modified: Duration,
+}
+
+/// What [`build`] indexed.
+pub(crate) struct Built {
+    pub(crate) files: usize,
+    /// Distinct trigrams.
+    pub(crate) trigrams: usize,
+    /// Files and directories that could not be read, reported as they came up.
+    pub(crate) skipped: usize,
+}
+
+/// Indexes the files below `root` that a search of it would look at and
+/// writes the index to `root/.minigrep-index`. What cannot be read is
+/// reported and left out, and the rest is indexed.
+pub(crate) fn build(root: &Path, config: &Config) -> io::Result<Built> {
+    let index_path = root.join(INDEX_FILE);
+    let mut index = Index { files: Vec::new(), postings: BTreeMap::new() };
+
+    let walked = walk::collect_files(root, config)?;
+    let mut skipped = walked.errors.len();
+    for (path, e) in &walked.errors {
+        eprintln!("minigrep: {}: {e}", path.display());
+    }
+    for path in walked.files {
+        if path == index_path {
+            continue;
+        }
+        match index_file(root, &path, config) {
+            Ok(Some((file, trigrams))) => {
+                let id = index.files.len() as u32;
+                for trigram in trigrams {
+                    index.postings.entry(trigram).or_default().push(id);
+                }
+                index.files.push(file);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("minigrep: {}: {e}", path.display());
+                skipped += 1;
+            }
+        }
+    }
+
+    index.write(&index_path)?;
+    Ok(Built { files: index.files.len(), trigrams: index.postings.len(), skipped })
+}
+
+/// Reads one file for the index; binary files are left out unless `--text` is given.
+fn index_file(root: &Path, path: &Path, config: &Config) -> io::Result<Option<(IndexedFile, HashSet<Trigram>)>> {
+    let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str) else {
+        eprintln!("minigrep: {}: not indexed, the path is not valid UTF-8", path.display());
+        return Ok(None);
+    };
+    let metadata = fs::metadata(path)?;
+    let mut contents = fs::read(path)?;
+    if config.search_zip {
+        let mut decompressed = Vec::new();
+        decompress::decompressed(&contents[..])?.read_to_end(&mut decompressed)?;
+        contents = decompressed;
+    }
+    if !config.text && is_binary(&mut &contents[..])? {
+        return Ok(None);
+    }
+
+    let file = IndexedFile { path: relative.to_string(), size: metadata.len(), modified: modified(&metadata) };
+    Ok(Some((file, trigrams(&contents))))
+}
+
+/// Narrows `files`, found by walking `root` as a search of it would, to the
+/// ones its index says may contain a match of `matcher`. Files that changed
+/// since the index was built are always kept, and files it does not know
+/// about are left out, as are the ones a new walk no longer finds, such as
+/// removed files or files filtered out by `--include` or `--exclude`.
+pub(crate) fn candidates(
+    root: &Path,
+    config: &Config,
+    matcher: &Matcher,
+    mut files: Vec<PathBuf>,
+) -> io::Result<Vec<PathBuf>> {
+    let index = Index::read(&root.join(INDEX_FILE))?;
+    // Lines without a match are found in files without the query too.
+    let selected = if config.invert_match { None } else { index.select(matcher) };
+
+    let indexed: HashMap<PathBuf, (u32, &IndexedFile)> =
+        index.files.iter().enumerate().map(|(id, file)| (root.join(&file.path), (id as u32, file))).collect();
+    files.retain(|path| {
+        let Some(&(id, file)) = indexed.get(path) else { return false };
+        let Ok(metadata) = fs::metadata(path) else { return false };
+        let changed = metadata.len() != file.size || modified(&metadata) != file.modified;
+        changed || selected.as_ref().is_none_or(|selected| selected.contains(&id))
+    });
+    Ok(files)
+}
+
+impl Index {
+    /// The files that can match `matcher`, or `None` when the index cannot
+    /// tell and every file has to be searched.
+    fn select(&self, matcher: &Matcher) -> Option<BTreeSet<u32>> {
+        let required = matcher.required_text().into_iter().map(|text| self.containing(&text?));
+        if matcher.requires_all() {
+            // Every pattern that can rule files out does so on its own.
+            required.flatten().reduce(|a, b| a.intersection(&b).copied().collect())
+        } else {
+            // A pattern that could match anywhere makes every file a candidate.
+            let sets = required.collect::<Option<Vec<_>>>()?;
+            sets.into_iter().reduce(|a, b| a.union(&b).copied().collect())
+        }
+    }
+
+    /// The files containing every trigram of the case-folded `text`, or
+    /// `None` when it is too short to have any.
+    fn containing(&self, text: &str) -> Option<BTreeSet<u32>> {
+        let mut trigrams = text.as_bytes().windows(3);
+        let first = trigrams.next()?;
+        let posting = |trigram: &[u8]| self.postings.get(trigram).map_or(&[][..], Vec::as_slice);
+
+        let mut files: BTreeSet<u32> = posting(first).iter().copied().collect();
+        for trigram in trigrams {
+            let others: BTreeSet<u32> = posting(trigram).iter().copied().collect();
+            files.retain(|id| others.contains(id));
+        }
+        Some(files)
+    }
+
+    /// Writes the index to `path`, replacing any index already there only
+    /// once the new one is complete.
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let temp = NamedTempFile::new_in(dir)?;
+        let mut out = BufWriter::new(temp.as_file());
+
+        out.write_all(MAGIC)?;
+        write_number(&mut out, self.files.len() as u64)?;
+        for file in &self.files {
+            write_number(&mut out, file.path.len() as u64)?;
+            out.write_all(file.path.as_bytes())?;
+            write_number(&mut out, file.size)?;
+            write_number(&mut out, file.modified.as_secs())?;
+            write_number(&mut out, file.modified.subsec_nanos().into())?;
+        }
+        write_number(&mut out, self.postings.len() as u64)?;
+        for (trigram, ids) in &self.postings {
+            out.write_all(trigram)?;
+            write_number(&mut out, ids.len() as u64)?;
+            // Ids are stored as the difference to the previous one, mostly small.
+            let mut previous = 0;
+            for &id in ids {
+                write_number(&mut out, (id - previous).into())?;
+                previous = id;
+            }
+        }
+        out.flush()?;
+        drop(out);
+
+        temp.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Reads the index written to `path`. A damaged index is reported as
+    /// invalid data, and cannot make it allocate more than its own size.
+    fn read(path: &Path) -> io::Result<Index> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut input = BufReader::new(file.take(size));
+
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_data("not a minigrep index"));
+        }
+
+        let mut files = Vec::new();
+        for _ in 0..read_length(&mut input)? {
+            let mut path = vec![0; read_length(&mut input)?];
+            input.read_exact(&mut path)?;
+            let path = String::from_utf8(path).map_err(|_| invalid_data("invalid path in index"))?;
+            let size = read_number(&mut input)?;
+            let secs = read_number(&mut input)?;
+            let nanos = u32::try_from(read_number(&mut input)?).ok().filter(|&nanos| nanos < 1_000_000_000);
+            let nanos = nanos.ok_or_else(|| invalid_data("invalid modification time in index"))?;
+            files.push(IndexedFile { path, size, modified: Duration::new(secs, nanos) });
+        }
+
+        let mut postings = BTreeMap::new();
+        for _ in 0..read_length(&mut input)? {
+            let mut trigram = [0; 3];
+            input.read_exact(&mut trigram)?;
+            let mut ids = Vec::new();
+            let mut id: u32 = 0;
+            for _ in 0..read_length(&mut input)? {
+                let next = u32::try_from(read_number(&mut input)?).ok().and_then(|step| id.checked_add(step));
+                let next = next.filter(|&next| (next as usize) < files.len());
+                id = next.ok_or_else(|| invalid_data("invalid file in index"))?;
+                ids.push(id);
+            }
+            postings.insert(trigram, ids);
+        }
+
+        Ok(Index { files, postings })
+    }
+}
+
+/// The trigrams of the case-folded text of every line of `text`.
+fn trigrams(text: &[u8]) -> HashSet<Trigram> {
+    let mut trigrams = HashSet::new();
+    let mut encoded = [0; 4];
+    for line in text.lines() {
+        let mut window = [0; 3];
+        let mut seen = 0;
+        for c in line.chars().flat_map(fold) {
+            for &byte in c.encode_utf8(&mut encoded).as_bytes() {
+                window = [window[1], window[2], byte];
+                seen += 1;
+                if seen >= 3 {
+                    trigrams.insert(window);
+                }
+            }
+        }
+    }
+    trigrams
+}
+
+fn modified(metadata: &fs::Metadata) -> Duration {
+    let modified = metadata.modified().ok();
+    modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok()).unwrap_or_default()
+}
+
+/// Writes `value` seven bits at a time, least significant first, with the
+/// high bit of every byte but the last set (LEB128).
+fn write_number(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_number(input: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("number too large in index"))
+}
+
+/// Reads the number of bytes or items that follow, each taking at least a
+/// byte, so that it cannot be more than the bytes left in `input`.
+fn read_length(input: &mut BufReader<Take<File>>) -> io::Result<usize> {
+    let length = read_number(input)?;
+    let left = input.get_ref().limit() + input.buffer().len() as u64;
+    match length <= left {
+        true => Ok(length as usize),
+        false => Err(invalid_data("index is cut short")),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    fn candidates_for(root: &Path, args: &[&str]) -> Vec<PathBuf> {
+        let config = test_config(args);
+        let matcher =
+            Matcher::new(&config.patterns, config.ignore_case, config.syntax(), config.all_patterns, config.anchor())
+                .unwrap();
+        let files = walk::collect_files(root, &config).unwrap().files;
+        candidates(root, &config, &matcher, files).unwrap()
+    }
+
+    #[test]
+    fn trigrams_are_case_folded_and_within_lines() {
+        let found = trigrams("Fro\nGS ß".as_bytes());
+        let expected: HashSet<Trigram> = [*b"fro", *b"gs ", *b"s s", *b" ss"].into_iter().collect();
+
+        assert_eq!(expected, found);
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        let mut out = Vec::new();
+        for value in [0, 127, 128, 300, u64::MAX] {
+            write_number(&mut out, value).unwrap();
+        }
+
+        let mut input = &out[..];
+        let read: Vec<u64> = (0..5).map(|_| read_number(&mut input).unwrap()).collect();
+        assert_eq!(vec![0, 127, 128, 300, u64::MAX], read);
+    }
+
+    #[test]
+    fn narrows_candidates_and_notices_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a frog\n").unwrap();
+        fs::write(root.join("b.txt"), "a bog\n").unwrap();
+        fs::write(root.join("c.txt"), "a FROG and a bog\n").unwrap();
+        let built = build(root, &test_config(&["minigrep", "index", "build", root.to_str().unwrap()])).unwrap();
+
+        let frog = candidates_for(root, &["minigrep", "frog", root.to_str().unwrap()]);
+        let any_case = candidates_for(root, &["minigrep", "-i", "frog", root.to_str().unwrap()]);
+        let any = candidates_for(root, &["minigrep", "-e", "frog", "-e", "bog", root.to_str().unwrap()]);
+        let all = candidates_for(root, &["minigrep", "-e", "frog", "-e", "bog", "--all", root.to_str().unwrap()]);
+        let short = candidates_for(root, &["minigrep", "og", root.to_str().unwrap()]);
+        fs::write(root.join("b.txt"), "a changed frog\n").unwrap();
+        let changed = candidates_for(root, &["minigrep", "toad", root.to_str().unwrap()]);
+
+        let paths = |names: &[&str]| names.iter().map(|name| root.join(name)).collect::<Vec<_>>();
+        assert_eq!((3, 0), (built.files, built.skipped));
+        // Case-sensitive queries are narrowed by their folded text, then verified by the search.
+        assert_eq!(paths(&["a.txt", "c.txt"]), frog);
+        assert_eq!(paths(&["a.txt", "c.txt"]), any_case);
+        assert_eq!(paths(&["a.txt", "b.txt", "c.txt"]), any);
+        assert_eq!(paths(&["c.txt"]), all);
+        assert_eq!(paths(&["a.txt", "b.txt", "c.txt"]), short);
+        assert_eq!(paths(&["b.txt"]), changed);
+    }
+
+    #[test]
+    fn damaged_index_is_invalid_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a frog\n").unwrap();
+        build(root, &test_config(&["minigrep", "index", "build", root.to_str().unwrap()])).unwrap();
+        let index_path = root.join(INDEX_FILE);
+        let written = fs::read(&index_path).unwrap();
+
+        let mut huge_path = MAGIC.to_vec();
+        write_number(&mut huge_path, 1).unwrap();
+        write_number(&mut huge_path, u64::MAX >> 1).unwrap();
+        // One file, with the trigram "fro" in file 0 and then in file 0 + u32::MAX.
+        let mut bad_id = MAGIC.to_vec();
+        write_number(&mut bad_id, 1).unwrap();
+        write_number(&mut bad_id, 1).unwrap();
+        bad_id.push(b'a');
+        write_number(&mut bad_id, 0).unwrap();
+        write_number(&mut bad_id, 0).unwrap();
+        write_number(&mut bad_id, 0).unwrap();
+        write_number(&mut bad_id, 1).unwrap();
+        bad_id.extend(b"fro");
+        write_number(&mut bad_id, 2).unwrap();
+        write_number(&mut bad_id, 0).unwrap();
+        write_number(&mut bad_id, u32::MAX.into()).unwrap();
+
+        let kind = |damaged: &[u8]| {
+            fs::write(&index_path, damaged).unwrap();
+            Index::read(&index_path).err().map(|e| e.kind())
+        };
+        assert_eq!(Some(io::ErrorKind::InvalidData), kind(&written[..written.len() - 1]));
+        assert_eq!(Some(io::ErrorKind::InvalidData), kind(&huge_path));
+        assert_eq!(Some(io::ErrorKind::InvalidData), kind(&bad_id));
+    }
+}
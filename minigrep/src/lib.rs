@@ -1,58 +1,306 @@
-use std::error::Error;
-use std::{env, fs};
-pub struct Config {
-    pub query: String,
-    pub file_path: String,
-    pub ignore_case : bool
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::num::NonZero;
+use std::path::Path;
+use std::thread;
+
+use memchr::{memchr, memmem, memrchr};
+use regex::Regex;
+
+mod config;
+mod decompress;
+mod error;
+mod index;
+mod matcher;
+mod mmap;
+mod parallel;
+mod printer;
+mod rewrite;
+mod settings;
+mod stream;
+mod tui;
+mod walk;
+mod watch;
+
+use crate::error::SearchError;
+use crate::printer::Printer;
+
+pub use crate::config::{ColorChoice, Command, Config, USAGE};
+pub use crate::error::Error;
+pub use crate::matcher::{Anchor, Matcher, Pattern, Syntax};
+pub use crate::stream::{is_binary, search_reader, search_reader_with_context, ContextMatches, Line, Matches};
+
+/// Searches as `config` says and returns whether anything was found, like
+/// the exit status of `grep`. With `--write`, finding something means
+/// replacing it.
+///
+/// Files of a tree that cannot be searched are reported as they come up and
+/// do not stop the search, but make it end with [`Error::Incomplete`]. With
+/// `--tui` the results are browsed instead of printed.
+pub fn run(config: Config) -> Result<bool, Error> {
+    if config.tui {
+        return tui::browse(&config);
+    }
+    let mut patterns = config.patterns.clone();
+    for path in &config.pattern_files {
+        let contents = fs::read_to_string(path).map_err(|source| Error::Io { path: path.into(), source })?;
+        patterns.extend(contents.lines().map(String::from));
+    }
+    let matcher = Matcher::new(&patterns, config.ignore_case, config.syntax(), config.all_patterns, config.anchor())?;
+    let is_match = |line: &[u8]| matcher.is_match(line) != config.invert_match;
+
+    let color = match config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    };
+
+    let mut printer = Printer::new(&config, &matcher, BufWriter::new(io::stdout().lock()), color);
+    let searched = match config.watch {
+        true => watch::watch(&config, &is_match, &mut printer),
+        false => search_input(&config, &is_match, &mut printer),
+    };
+    match searched.and_then(|()| printer.finish().map_err(Error::Output)) {
+        // The reader went away, e.g. `minigrep ... | head`; nothing left to do.
+        Err(e) if e.is_broken_pipe() => Ok(printer.matched()),
+        Err(e) => Err(e),
+        // As with grep, a line found with -q is all that was asked for.
+        Ok(()) if printer.skipped() > 0 && !printer.done() => Err(Error::Incomplete { skipped: printer.skipped() }),
+        Ok(()) => Ok(printer.matched()),
+    }
+}
+
+/// Builds the index of the directory `config.file_path` searched by
+/// `minigrep index query`, see [`Command::BuildIndex`]. Files that cannot be
+/// read are left out of it, and make it end with [`Error::Incomplete`].
+pub fn build_index(config: Config) -> Result<(), Error> {
+    let root = Path::new(&config.file_path);
+    let io_error = |source| Error::Io { path: root.to_path_buf(), source };
+    if !root.is_dir() {
+        return Err(io_error(io::Error::new(io::ErrorKind::NotADirectory, "not a directory")));
+    }
+
+    let built = index::build(root, &config).map_err(io_error)?;
+    let (files, trigrams) = (built.files, built.trigrams);
+    println!("Indexed {files} files ({trigrams} trigrams) into {}", root.join(index::INDEX_FILE).display());
+    match built.skipped {
+        0 => Ok(()),
+        skipped => Err(Error::Incomplete { skipped }),
+    }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents: String = fs::read_to_string(config.file_path)?;
+/// Searches the input named by `config.file_path`: standard input for `-`,
+/// every file below it for a directory, or else the single file itself.
+fn search_input(
+    config: &Config,
+    is_match: &(dyn Fn(&[u8]) -> bool + Sync),
+    printer: &mut Printer<impl Write>,
+) -> Result<(), Error> {
+    if config.file_path == "-" {
+        let name = Path::new("(standard input)");
+        let stdin = io::stdin().lock();
+        return search_buffered(config, is_match, printer, name, stdin, false).map_err(|e| e.at(name));
+    }
+
+    let path = Path::new(&config.file_path);
+    let io_error = |source| Error::Io { path: path.to_path_buf(), source };
+    match (path.is_dir(), config.indexed) {
+        (false, false) => return search_path(config, is_match, printer, path, false).map_err(|e| e.at(path)),
+        // Only a directory has an index.
+        (false, true) => return Err(io_error(io::Error::new(io::ErrorKind::NotADirectory, "not a directory"))),
+        (true, _) => {}
+    }
 
-    let result = if config.ignore_case {
-        search(&config.query, &contents)
-    }else {
-        search_case_insensitive(&config.query, &contents)
+    // Directories are walked recursively, and every match carries its
+    // location. With an index, only the files it cannot rule out are searched.
+    let walked = walk::collect_files(path, config).map_err(io_error)?;
+    let files = match config.indexed {
+        true => index::candidates(path, config, printer.matcher(), walked.files).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::MissingIndex { path: path.join(index::INDEX_FILE) },
+            _ => Error::Io { path: path.join(index::INDEX_FILE), source: e },
+        })?,
+        false => walked.files,
     };
+    for (path, e) in walked.errors {
+        printer.skip_file(&path, SearchError::Read(e)).map_err(Error::Output)?;
+    }
+    printer.show_names = true;
 
-    for line in result{
-        println!("{line}");
+    // From here on, files that cannot be read are skipped, so what is left
+    // to stop the search is failing to write the results.
+    let threads = config.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZero::get));
+    if threads > 1 && files.len() > 1 {
+        return parallel::search_files(config, is_match, printer, &files, threads).map_err(Error::Output);
+    }
+
+    for path in files {
+        if printer.done() {
+            break;
+        }
+        if let Err(e) = search_path(config, is_match, printer, &path, true) {
+            printer.skip_file(&path, e).map_err(Error::Output)?;
+        }
     }
 
     Ok(())
 }
 
-impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("Not enough arguments");
-        }
-        let query = args[1].clone(); // Clone to take ownership
-        let file_path = args[2].clone(); // Clone to take ownership
+/// Searches a single file, printing what it finds with `printer`, or with
+/// `--write` rewrites it.
+fn search_path(
+    config: &Config,
+    is_match: &dyn Fn(&[u8]) -> bool,
+    printer: &mut Printer<impl Write>,
+    path: &Path,
+    walked: bool,
+) -> Result<(), SearchError> {
+    if let (true, Some(replacement)) = (config.write, &config.replace) {
+        return match rewrite::rewrite_file(config, printer.matcher(), path, replacement).map_err(SearchError::Read)? {
+            Some(replacements) => printer.print_rewritten(path, replacements).map_err(SearchError::Write),
+            None if walked => Ok(()),
+            None => {
+                let message = format!("{}: binary file not rewritten, use --text to force", path.display());
+                Err(SearchError::Read(io::Error::new(io::ErrorKind::InvalidData, message)))
+            }
+        };
+    }
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
-        Ok(Config { query, file_path, ignore_case })
+    let file = File::open(path).map_err(SearchError::Read)?;
+    match mmap::map(config, &file) {
+        Some(map) => search_buffered(config, is_match, printer, path, &map[..], walked),
+        None => search_buffered(config, is_match, printer, path, BufReader::new(file), walked),
     }
 }
 
+/// Searches an open input named `name`, decompressed first with `-z`.
+fn search_buffered(
+    config: &Config,
+    is_match: &dyn Fn(&[u8]) -> bool,
+    printer: &mut Printer<impl Write>,
+    name: &Path,
+    reader: impl BufRead,
+    walked: bool,
+) -> Result<(), SearchError> {
+    if config.search_zip {
+        let reader = decompress::decompressed(reader).map_err(SearchError::Read)?;
+        return search_text(config, is_match, printer, name, reader, walked);
+    }
+    search_text(config, is_match, printer, name, reader, walked)
+}
+
+/// Searches the (decompressed) text of an input named `name`. Unless `--text`
+/// is given, binary input is skipped when it was found by walking a tree, and
+/// otherwise only reported as matching.
+fn search_text(
+    config: &Config,
+    is_match: &dyn Fn(&[u8]) -> bool,
+    printer: &mut Printer<impl Write>,
+    name: &Path,
+    mut reader: impl BufRead,
+    walked: bool,
+) -> Result<(), SearchError> {
+    let binary = !config.text && is_binary(&mut reader).map_err(SearchError::Read)?;
+    if binary && walked {
+        return Ok(());
+    }
+
+    let mut lines = search_reader_with_context(reader, is_match, config.before_context, config.after_context);
+    if let Some(max) = config.max_count {
+        lines = lines.max_count(max);
+    }
+    if let (false, Some(literal)) = (config.invert_match, printer.matcher().required_literal()) {
+        lines = lines.skip_lines_without(literal.as_bytes());
+    }
+    if binary {
+        printer.print_binary(name, lines)
+    } else {
+        printer.print_file(name, lines)
+    }
+}
+
+/// Returns the lines of `contains` that contain `query`.
+///
+/// Rather than splitting `contains` into lines and looking at each, the
+/// whole text is scanned for `query` with a vectorized substring search, and
+/// line boundaries are only looked for around each hit.
 pub fn search<'a>(query: &'a str, contains: &'a str) -> Vec<&'a str> {
-    let mut result = Vec::new();
-    for line in contains.lines() {
-        if line.contains(query) {
-            result.push(line);
+    let text = contains.as_bytes();
+    let finder = memmem::Finder::new(query);
+    let mut found = Vec::new();
+    let mut from = 0;
+    while from < text.len() {
+        let Some(hit) = finder.find(&text[from..]).map(|at| from + at) else { break };
+        let start = memrchr(b'\n', &text[..hit]).map_or(0, |newline| newline + 1);
+        let end = memchr(b'\n', &text[hit..]).map_or(text.len(), |newline| hit + newline);
+        // As with `str::lines`, a "\r" before the "\n" is not part of the line.
+        let line = &contains[start..end];
+        let line = if end < text.len() { line.strip_suffix('\r').unwrap_or(line) } else { line };
+        // A hit running into the line ending is no match, nor is any later one on the line.
+        if hit + query.len() <= start + line.len() {
+            found.push(line);
         }
+        from = end + 1;
     }
+    found
+}
 
-    result
+/// Returns the lines containing text within `max_distance` edits (characters
+/// inserted, deleted or substituted) of `query`, closest first. Lines equally
+/// close keep their order.
+pub fn search_fuzzy<'a>(query: &str, contains: &'a str, max_distance: usize) -> Vec<&'a str> {
+    rank_fuzzy(query, contains, max_distance, false)
+}
+
+/// Like [`search_fuzzy`], but ignoring case as [`search_case_insensitive`]
+/// does, so that "STRASSE" is no edit away from "straße".
+pub fn search_fuzzy_case_insensitive<'a>(query: &str, contains: &'a str, max_distance: usize) -> Vec<&'a str> {
+    rank_fuzzy(query, contains, max_distance, true)
+}
+
+fn rank_fuzzy<'a>(query: &str, contains: &'a str, max_distance: usize, ignore_case: bool) -> Vec<&'a str> {
+    // Matched as with `--fuzzy`, so that the library and the command line agree.
+    let pattern = Pattern::fuzzy(query, ignore_case, max_distance);
+    let mut found: Vec<(usize, &str)> = contains
+        .lines()
+        .filter_map(|line| pattern.distance(line.as_bytes(), Anchor::Anywhere).map(|distance| (distance, line)))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect();
+    found.sort_by_key(|&(distance, _)| distance);
+    found.into_iter().map(|(_, line)| line).collect()
 }
 
 pub fn search_case_insensitive<'a>(query : &'a str, contains : &'a str) -> Vec<&'a str>{
-    let query = query.to_lowercase();
-    let mut result = Vec::new();
+    let pattern = Pattern::ignore_case(query);
 
-    for line in contains.lines() {
-        if line.to_lowercase().contains(&query) {
-            result.push(line);
+    search_numbered(contains, |line| pattern.is_match(line.as_bytes()))
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+pub fn search_regex<'a>(re: &Regex, contains: &'a str) -> Vec<&'a str> {
+    search_numbered(contains, |line| re.is_match(line))
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+pub fn search_matcher<'a>(matcher: &Matcher, contains: &'a str) -> Vec<&'a str> {
+    search_numbered(contains, |line| matcher.is_match(line.as_bytes()))
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// Returns every line accepted by `is_match` together with its 1-based line number.
+pub fn search_numbered(contains: &str, is_match: impl Fn(&str) -> bool) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    for (index, line) in contains.lines().enumerate() {
+        if is_match(line) {
+            result.push((index + 1, line));
         }
     }
 
@@ -75,6 +323,17 @@ mod tests {
         assert_eq!(vec!["The road to hell"], search(query, contains));
     }
 
+    #[test]
+    fn search_finds_lines_around_hits() {
+        let contains = "frog\r\nbog\nfrog and frog\nfr\nog\nlast frog";
+
+        assert_eq!(vec!["frog", "frog and frog", "last frog"], search("frog", contains));
+        assert_eq!(vec!["frog and frog"], search("og and", contains));
+        assert!(search("fr\nog", contains).is_empty());
+        assert!(search("frog\r", contains).is_empty());
+        assert_eq!(contains.lines().collect::<Vec<_>>(), search("", contains));
+    }
+
     #[test]
     fn case_insesitive() {
         let query = "rust";
@@ -89,4 +348,110 @@ Trust me.";
             search_case_insensitive(query, contains)
         )
     }
+
+    #[test]
+    fn case_insensitive_german() {
+        let contains = "\
+Die Straße ist lang.
+DIE STRASSE IST LANG.
+Der Strauß ist groß.";
+
+        assert_eq!(vec!["Die Straße ist lang.", "DIE STRASSE IST LANG."],
+            search_case_insensitive("strasse", contains)
+        );
+        assert_eq!(vec!["Der Strauß ist groß."], search_case_insensitive("GROSS", contains));
+    }
+
+    #[test]
+    fn case_insensitive_turkish_and_greek() {
+        let contains = "\
+İSTANBUL
+ıstanbul
+Ankara
+ΟΔΥΣΣΕΥΣ";
+
+        assert_eq!(vec!["İSTANBUL", "ıstanbul"], search_case_insensitive("Istanbul", contains));
+        assert_eq!(vec!["ΟΔΥΣΣΕΥΣ"], search_case_insensitive("οδυσσευς", contains));
+    }
+
+    #[test]
+    fn fuzzy_query_ranked_by_distance() {
+        let contains = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.
+Rusty nails.";
+
+        assert_eq!(vec!["Trust me.", "Rust:", "Rusty nails."],
+            search_fuzzy("rust", contains, 1)
+        );
+        assert_eq!(vec!["Rust:", "Rusty nails."], search_fuzzy("Rust", contains, 0));
+        assert_eq!(vec!["Pick three."], search_fuzzy("thee", contains, 1));
+        assert_eq!(vec!["Rust:", "Trust me.", "Rusty nails."], search_fuzzy_case_insensitive("RUST", contains, 0));
+        assert_eq!(vec!["Trust me.", "Rust:", "Rusty nails."], search_fuzzy_case_insensitive("TRUST", contains, 1));
+    }
+
+    #[test]
+    fn regex_query() {
+        let re = Regex::new(r"^\w+:$|three").unwrap();
+
+        let contains = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(vec!["Rust:", "Pick three."], search_regex(&re, contains));
+    }
+
+    #[test]
+    fn several_patterns() {
+        let patterns = vec!["rust".to_string(), "three".to_string()];
+        let contains = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        let any = Matcher::new(&patterns, false, Syntax::Literal, false, Anchor::Anywhere).unwrap();
+        assert_eq!(vec!["Pick three.", "Trust me."], search_matcher(&any, contains));
+
+        let all = Matcher::new(&patterns, true, Syntax::Literal, true, Anchor::Anywhere).unwrap();
+        assert!(search_matcher(&all, contains).is_empty());
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let contains = "\
+Then there's a pair of us - don't tell!
+To tell your name the livelong day
+The End";
+        let query = |query: &str, ignore_case, anchor| {
+            Matcher::new(&[query.to_string()], ignore_case, Syntax::Literal, false, anchor).unwrap()
+        };
+
+        assert_eq!(vec!["To tell your name the livelong day"],
+            search_matcher(&query("the", false, Anchor::Word), contains)
+        );
+        assert_eq!(vec!["To tell your name the livelong day", "The End"],
+            search_matcher(&query("THE", true, Anchor::Word), contains)
+        );
+        assert_eq!(vec!["The End"], search_matcher(&query("the end", true, Anchor::Line), contains));
+        assert!(search_matcher(&query("the end", false, Anchor::Line), contains).is_empty());
+    }
+
+    #[test]
+    fn numbered_lines() {
+        let contains = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(
+            vec![(1, "Rust:"), (4, "Trust me.")],
+            search_numbered(contains, |line| line.contains("ust"))
+        );
+    }
 }
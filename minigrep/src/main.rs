@@ -1,20 +1,38 @@
 use std::env;
 use std::process;
 
-use minigrep::Config;
+use minigrep::{Command, Error, USAGE};
 
 fn main() {
-    println!("Minigrep Project");
-
     let args: Vec<String> = env::args().collect();
 
-    let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-    });
+    let result = match Command::parse(&args) {
+        Ok(Command::Search(config)) => minigrep::run(config),
+        Ok(Command::BuildIndex(config)) => minigrep::build_index(config).map(|()| true),
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("minigrep {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err @ Error::Usage(_)) => {
+            eprintln!("Problem parsing arguments: {err}");
+            eprintln!("Try 'minigrep --help' for more information.");
+            process::exit(2);
+        }
+        // A configuration file that cannot be used is no fault of the arguments.
+        Err(err) => Err(err),
+    };
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error  : {e}");
-        process::exit(1);
+    // Like grep: 0 when something was found, 1 when nothing was, 2 on errors.
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Application error  : {e}");
+            process::exit(2);
+        }
     }
 }
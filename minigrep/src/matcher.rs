@@ -0,0 +1,650 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+
+use bstr::ByteSlice;
+use memchr::memmem;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::error::Error;
+
+/// A set of patterns, matching a line when any of them (or, with `all`,
+/// every one of them) occurs in it.
+///
+/// Lines are bytes: they need not be valid UTF-8, and every span returned
+/// is a byte range of the line as given.
+pub struct Matcher {
+    patterns: Vec<Pattern>,
+    all: bool,
+    anchor: Anchor,
+}
+
+/// Where in a line a pattern is allowed to match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    /// Anywhere, as a substring.
+    Anywhere,
+    /// Only as a whole word, as with `grep -w`: the characters on either side
+    /// of the match, if any, must not be word characters (`\w`).
+    Word,
+    /// Only when the match covers the whole line, as with `grep -x`.
+    Line,
+}
+
+/// How a query is to be understood.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    /// As the text to find.
+    Literal,
+    /// As a regular expression.
+    Regex,
+    /// As text to find with up to this many characters inserted, deleted or
+    /// substituted, i.e. within this Levenshtein distance.
+    Fuzzy(usize),
+}
+
+impl Matcher {
+    pub fn new(
+        queries: &[String],
+        ignore_case: bool,
+        syntax: Syntax,
+        all: bool,
+        anchor: Anchor,
+    ) -> Result<Matcher, Error> {
+        let patterns = queries
+            .iter()
+            .map(|query| match Pattern::new(query, ignore_case, syntax)? {
+                // The leftmost match of a regex need not be the longest one, so
+                // rather than checking its span the regex itself is anchored.
+                Pattern::Regex(_) if anchor == Anchor::Line => {
+                    Pattern::new(&format!("^(?:{query})$"), ignore_case, syntax)
+                }
+                // Half word boundaries only look outside the match, so a query
+                // starting or ending with a non-word character works as it does
+                // for literals. Like `\w`, they are Unicode-aware.
+                Pattern::Regex(_) if anchor == Anchor::Word => {
+                    Pattern::new(&format!(r"\b{{start-half}}(?:{query})\b{{end-half}}"), ignore_case, syntax)
+                }
+                pattern => Ok(pattern),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Matcher { patterns, all, anchor })
+    }
+
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        let matches = |pattern: &Pattern| pattern.find_anchored(line, 0, self.anchor).is_some();
+        if self.all {
+            // An empty set matches nothing, as with `grep -f /dev/null`.
+            !self.patterns.is_empty() && self.patterns.iter().all(matches)
+        } else {
+            self.patterns.iter().any(matches)
+        }
+    }
+
+    /// Returns the byte ranges in `line` covered by any of the patterns,
+    /// sorted and with overlapping matches merged.
+    pub fn find_iter(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let mut spans: Vec<Range<usize>> =
+            self.patterns.iter().flat_map(|pattern| pattern.find_all(line, self.anchor)).collect();
+        spans.sort_by_key(|span| span.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                _ => merged.push(span),
+            }
+        }
+        merged
+    }
+
+    /// How close `line` comes to the fuzzy queries: the fewest edits with
+    /// which any of them matches where the anchor allows. `None` when there
+    /// are no fuzzy queries.
+    pub fn distance(&self, line: &[u8]) -> Option<usize> {
+        self.patterns.iter().filter_map(|pattern| pattern.distance(line, self.anchor)).min()
+    }
+
+    /// For each pattern, text that every line it matches contains once both
+    /// are case-folded, or `None` when there is no such text, as for regexes
+    /// and fuzzy queries. Used to rule out files with an index.
+    pub(crate) fn required_text(&self) -> Vec<Option<String>> {
+        let required = |pattern: &Pattern| match pattern {
+            Pattern::Literal(query) => Some(query.chars().flat_map(fold).collect()),
+            Pattern::IgnoreCase(query) => Some(query.iter().collect()),
+            Pattern::Regex(_) | Pattern::Fuzzy { .. } => None,
+        };
+        self.patterns.iter().map(required).collect()
+    }
+
+    /// Text that every line the matcher matches contains exactly as it is,
+    /// if there is such text: a case-sensitive literal query that is the only
+    /// one, or one of several that all have to match.
+    pub(crate) fn required_literal(&self) -> Option<&str> {
+        fn literal(pattern: &Pattern) -> Option<&str> {
+            match pattern {
+                Pattern::Literal(query) => Some(query),
+                _ => None,
+            }
+        }
+        match self.patterns.as_slice() {
+            [pattern] => literal(pattern),
+            patterns if self.all => patterns.iter().find_map(literal),
+            _ => None,
+        }
+    }
+
+    /// Whether a line has to match every pattern, rather than any of them.
+    pub(crate) fn requires_all(&self) -> bool {
+        self.all
+    }
+
+    /// Returns the spans [`find_iter`](Self::find_iter) finds in `line`, each
+    /// with the text that replaces it. When the matcher is a single regex,
+    /// `$1` or `$name` in `replacement` stand for the groups it captured;
+    /// otherwise `replacement` is used as it is.
+    pub fn replacements(&self, line: &[u8], replacement: &str) -> Vec<(Range<usize>, Vec<u8>)> {
+        let spans = self.find_iter(line).into_iter();
+        match self.patterns.as_slice() {
+            [Pattern::Regex(re)] => spans
+                .map(|span| {
+                    let mut replaced = Vec::new();
+                    // Matching again from the start of the span finds the same match.
+                    if let Some(captures) = re.captures_at(line, span.start) {
+                        captures.expand(replacement.as_bytes(), &mut replaced);
+                    }
+                    (span, replaced)
+                })
+                .collect(),
+            _ => spans.map(|span| (span, replacement.as_bytes().to_vec())).collect(),
+        }
+    }
+}
+
+/// The compiled form of a single query, able to say where in a line it matches.
+pub enum Pattern {
+    Literal(String),
+    /// Matches the case-folded query against the line, folding the line one
+    /// character at a time as it is compared. See [`fold`].
+    IgnoreCase(Vec<char>),
+    Regex(Regex),
+    /// Matches text within `max` edits of the query, whose characters are
+    /// folded, as are those of the line, when `ignore_case` is set.
+    Fuzzy { query: Vec<char>, max: usize, ignore_case: bool },
+}
+
+impl Pattern {
+    pub fn new(query: &str, ignore_case: bool, syntax: Syntax) -> Result<Pattern, Error> {
+        match syntax {
+            Syntax::Regex => {
+                let re = RegexBuilder::new(query)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .map_err(|source| Error::Regex { query: query.to_string(), source })?;
+                Ok(Pattern::Regex(re))
+            }
+            Syntax::Fuzzy(max) => Ok(Pattern::fuzzy(query, ignore_case, max)),
+            Syntax::Literal if ignore_case => Ok(Pattern::ignore_case(query)),
+            Syntax::Literal => Ok(Pattern::Literal(query.to_string())),
+        }
+    }
+
+    /// A literal query that matches regardless of case, using full Unicode
+    /// case folding: "STRASSE" matches "straße" and "ΣΟΦΟΣ" matches "σοφος".
+    pub fn ignore_case(query: &str) -> Pattern {
+        Pattern::IgnoreCase(query.chars().flat_map(fold).collect())
+    }
+
+    /// A query that matches text within `max` edits of it, see [`Syntax::Fuzzy`].
+    pub fn fuzzy(query: &str, ignore_case: bool, max: usize) -> Pattern {
+        let query = match ignore_case {
+            true => query.chars().flat_map(fold).collect(),
+            false => query.chars().collect(),
+        };
+        Pattern::Fuzzy { query, max, ignore_case }
+    }
+
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        match self {
+            Pattern::Literal(query) => memmem::find(line, query.as_bytes()).is_some(),
+            Pattern::IgnoreCase(query) => find_folded(line, query, 0).is_some(),
+            Pattern::Regex(re) => re.is_match(line),
+            Pattern::Fuzzy { query, max, ignore_case } => {
+                let columns = columns(line, *ignore_case);
+                edit_distances(query, columns.iter().map(|(c, _)| *c), false).into_iter().min() <= Some(*max)
+            }
+        }
+    }
+
+    /// The fewest edits with which a fuzzy query matches `line` where `anchor`
+    /// allows, even if that is more than it allows. `None` for other queries,
+    /// and for `Anchor::Word` in a line without words.
+    pub fn distance(&self, line: &[u8], anchor: Anchor) -> Option<usize> {
+        let Pattern::Fuzzy { query, ignore_case, .. } = self else { return None };
+        match anchor {
+            Anchor::Anywhere => {
+                let columns = columns(line, *ignore_case);
+                edit_distances(query, columns.iter().map(|(c, _)| *c), false).into_iter().min()
+            }
+            Anchor::Word => fuzzy_words(line, query, *ignore_case, 0).into_iter().map(|(_, distance)| distance).min(),
+            Anchor::Line => {
+                let columns = columns(line, *ignore_case);
+                edit_distances(query, columns.iter().map(|(c, _)| *c), true).pop()
+            }
+        }
+    }
+
+    /// Finds the leftmost match that starts at or after byte `from`.
+    fn find_at(&self, line: &[u8], from: usize) -> Option<Range<usize>> {
+        match self {
+            Pattern::Literal(query) => {
+                memmem::find(&line[from..], query.as_bytes()).map(|start| from + start..from + start + query.len())
+            }
+            Pattern::IgnoreCase(query) => find_folded(line, query, from),
+            Pattern::Regex(re) => re.find_at(line, from).map(|found| found.range()),
+            Pattern::Fuzzy { query, max, ignore_case } => find_fuzzy(line, query, *max, *ignore_case, from),
+        }
+    }
+
+    /// Like `find_at`, but only finds matches where `anchor` allows them.
+    /// Regexes are expected to be anchored already, see [`Matcher::new`].
+    fn find_anchored(&self, line: &[u8], mut from: usize, anchor: Anchor) -> Option<Range<usize>> {
+        match (anchor, self) {
+            (Anchor::Anywhere, _) => self.find_at(line, from),
+            (Anchor::Line, _) if from > 0 => None,
+            (Anchor::Line, Pattern::Literal(query)) => (line == query.as_bytes()).then_some(0..line.len()),
+            (Anchor::Line, Pattern::IgnoreCase(query)) => {
+                let folded = line.chars().flat_map(fold);
+                folded.eq(query.iter().copied()).then_some(0..line.len())
+            }
+            (Anchor::Line, Pattern::Regex(re)) => re.is_match(line).then_some(0..line.len()),
+            (Anchor::Line, Pattern::Fuzzy { query, max, ignore_case }) => {
+                let columns = columns(line, *ignore_case);
+                let distances = edit_distances(query, columns.iter().map(|(c, _)| *c), true);
+                (distances.last() <= Some(max)).then_some(0..line.len())
+            }
+            (Anchor::Word, Pattern::Regex(_)) => self.find_at(line, from),
+            // The closest text need not be a word, so each word is measured on its own.
+            (Anchor::Word, Pattern::Fuzzy { query, max, ignore_case }) => {
+                let words: Vec<_> = fuzzy_words(line, query, *ignore_case, from)
+                    .into_iter()
+                    .filter(|(_, distance)| distance <= max)
+                    .collect();
+                let start = words.first()?.0.start;
+                // As with `find_fuzzy`, the closest, and then the longest, text wins.
+                let (span, _) = words
+                    .into_iter()
+                    .take_while(|(span, _)| span.start == start)
+                    .min_by_key(|(span, distance)| (*distance, Reverse(span.end)))?;
+                Some(span)
+            }
+            (Anchor::Word, _) => loop {
+                let span = self.find_at(line, from)?;
+                let before = line[..span.start].chars().next_back();
+                let after = line[span.end..].chars().next();
+                if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+                    return Some(span);
+                }
+                // Another match may start inside this one, as "ab" does in "aab ab".
+                from = next_char(line, span.start)?;
+            },
+        }
+    }
+
+    /// Returns the byte ranges of the non-empty, non-overlapping matches in
+    /// `line`, from left to right.
+    pub fn find_iter(&self, line: &[u8]) -> Vec<Range<usize>> {
+        self.find_all(line, Anchor::Anywhere)
+    }
+
+    fn find_all(&self, line: &[u8], anchor: Anchor) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut from = 0;
+        while let Some(span) = self.find_anchored(line, from, anchor) {
+            if span.is_empty() {
+                // Step over an empty match so that the search moves on.
+                match next_char(line, span.end) {
+                    Some(next) => from = next,
+                    None => break,
+                }
+            } else {
+                from = span.end;
+                spans.push(span);
+            }
+        }
+        spans
+    }
+}
+
+/// The full case folding of `c`, which may be several characters ('ß'
+/// folds to "ss"). Folding both sides makes the comparison symmetric, so
+/// 'ẞ', 'ß' and "SS" all compare equal, as do 'ς', 'σ' and 'Σ'.
+///
+/// The Turkish dotted capital 'İ' and dotless small 'ı' fold to a plain 'i',
+/// so that "İstanbul" and "ISTANBUL" both match "istanbul".
+pub(crate) fn fold(c: char) -> impl Iterator<Item = char> {
+    let c = if matches!(c, 'İ' | 'ı') { 'i' } else { c };
+    // Lowercasing first takes 'ẞ' to 'ß', which uppercases to "SS".
+    c.to_lowercase().flat_map(char::to_uppercase).flat_map(char::to_lowercase)
+}
+
+/// The characters of `line`, folded when `ignore_case` is set, each with the
+/// bytes of the character of `line` it came from.
+fn columns(line: &[u8], ignore_case: bool) -> Vec<(char, Range<usize>)> {
+    let mut columns = Vec::with_capacity(line.len());
+    for (start, end, c) in line.char_indices() {
+        match ignore_case {
+            true => columns.extend(fold(c).map(|folded| (folded, start..end))),
+            false => columns.push((c, start..end)),
+        }
+    }
+    columns
+}
+
+/// For every prefix of `text`, the fewest edits that turn `query` into a
+/// substring of `text` ending where the prefix ends (Sellers' algorithm), or
+/// with `anchored` into the whole prefix (plain Levenshtein distance).
+/// Element `k` is for the first `k` characters of `text`.
+fn edit_distances(query: &[char], text: impl Iterator<Item = char>, anchored: bool) -> Vec<usize> {
+    // One column of the usual dynamic programming table, one row per character of the query.
+    let mut column: Vec<usize> = (0..=query.len()).collect();
+    let mut distances = vec![query.len()];
+    for (index, c) in text.enumerate() {
+        let mut diagonal = column[0];
+        column[0] = if anchored { index + 1 } else { 0 };
+        for row in 1..=query.len() {
+            let substituted = diagonal + usize::from(query[row - 1] != c);
+            diagonal = column[row];
+            column[row] = substituted.min(column[row] + 1).min(column[row - 1] + 1);
+        }
+        distances.push(column[query.len()]);
+    }
+    distances
+}
+
+/// Finds the first text within `max` edits of `query` that starts at or
+/// after byte `from`. It extends for as long as the distance does not grow,
+/// so that "frg" finds "frog" whole rather than "fr", and starts as late as
+/// the smallest distance allows.
+fn find_fuzzy(line: &[u8], query: &[char], max: usize, ignore_case: bool, from: usize) -> Option<Range<usize>> {
+    // Deleting the whole query is within reach, so everything matches.
+    if query.len() <= max {
+        return Some(from..from);
+    }
+    let columns = columns(&line[from..], ignore_case);
+    let forward = edit_distances(query, columns.iter().map(|(c, _)| *c), false);
+    let mut end = forward.iter().position(|&distance| distance <= max)?;
+    while forward.get(end + 1).is_some_and(|&next| next <= forward[end]) {
+        end += 1;
+    }
+
+    let reversed: Vec<char> = query.iter().rev().copied().collect();
+    let backward = edit_distances(&reversed, columns[..end].iter().rev().map(|(c, _)| *c), true);
+    let (_, length) = backward.iter().enumerate().skip(1).map(|(length, &distance)| (distance, length)).min()?;
+
+    Some(from + columns[end - length].1.start..from + columns[end - 1].1.end)
+}
+
+/// Every stretch of `line` at or after byte `from` with no word characters
+/// right before or after it, as [`Anchor::Word`] requires, with the fewest
+/// edits that turn `query` into it. They are ordered by where they start.
+fn fuzzy_words(line: &[u8], query: &[char], ignore_case: bool, from: usize) -> Vec<(Range<usize>, usize)> {
+    let columns = columns(line, ignore_case);
+    // The columns where a character of the line starts, and the end of the line.
+    let boundaries: Vec<usize> = (0..=columns.len())
+        .filter(|&k| k == 0 || k == columns.len() || columns[k].1.start != columns[k - 1].1.start)
+        .collect();
+    let byte = |k: usize| columns.get(k).map_or(line.len(), |(_, bytes)| bytes.start);
+
+    let mut words = Vec::new();
+    for (n, &first) in boundaries.iter().enumerate() {
+        let start = byte(first);
+        if start < from || line[..start].chars().next_back().is_some_and(is_word_char) {
+            continue;
+        }
+        let distances = edit_distances(query, columns[first..].iter().map(|(c, _)| *c), true);
+        for &last in &boundaries[n..] {
+            let end = byte(last);
+            if !line[end..].chars().next().is_some_and(is_word_char) {
+                words.push((start..end, distances[last - first]));
+            }
+        }
+    }
+    words
+}
+
+/// Whether `c` is part of a word for [`Anchor::Word`]. This is exactly `\w`
+/// in a regex, combining marks included, so that literal and regex queries
+/// find the same words.
+fn is_word_char(c: char) -> bool {
+    regex_syntax::is_word_character(c)
+}
+
+/// The byte position of the character after the one starting at `at`.
+fn next_char(line: &[u8], at: usize) -> Option<usize> {
+    line[at..].char_indices().next().map(|(_, end, _)| at + end)
+}
+
+/// Finds the first match of the folded `query` in `line` that starts at or
+/// after byte `from`. The span covers whole characters of `line`, which is
+/// folded lazily, so nothing is allocated. Invalid UTF-8 compares as U+FFFD.
+fn find_folded(line: &[u8], query: &[char], from: usize) -> Option<Range<usize>> {
+    if query.is_empty() {
+        return Some(from..from);
+    }
+    let rest = &line[from..];
+    rest.char_indices().find_map(|(start, _, _)| {
+        let len = folded_prefix_len(&rest[start..], query)?;
+        Some(from + start..from + start + len)
+    })
+}
+
+/// Returns how many bytes at the start of `text` fold to `query`. A match may
+/// end partway through the folding of a character, as "s" does in 'ß'.
+fn folded_prefix_len(text: &[u8], query: &[char]) -> Option<usize> {
+    let mut wanted = query.iter().peekable();
+    for (_, end, ch) in text.char_indices() {
+        for folded in fold(ch) {
+            match wanted.next() {
+                Some(&want) if want == folded => {}
+                Some(_) => return None,
+                None => break,
+            }
+        }
+        if wanted.peek().is_none() {
+            return Some(end);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax(regex: bool) -> Syntax {
+        if regex { Syntax::Regex } else { Syntax::Literal }
+    }
+
+    fn matcher(queries: &[&str], ignore_case: bool, regex: bool, all: bool) -> Matcher {
+        let queries: Vec<String> = queries.iter().map(|s| s.to_string()).collect();
+        Matcher::new(&queries, ignore_case, syntax(regex), all, Anchor::Anywhere).unwrap()
+    }
+
+    fn anchored(query: &str, ignore_case: bool, regex: bool, anchor: Anchor) -> Matcher {
+        Matcher::new(&[query.to_string()], ignore_case, syntax(regex), false, anchor).unwrap()
+    }
+
+    fn fuzzy(query: &str, ignore_case: bool, max: usize, anchor: Anchor) -> Matcher {
+        Matcher::new(&[query.to_string()], ignore_case, Syntax::Fuzzy(max), false, anchor).unwrap()
+    }
+
+    #[test]
+    fn literal_spans() {
+        let matcher = matcher(&["og"], false, false, false);
+
+        assert_eq!(vec![2..4, 11..13], matcher.find_iter(b"frog or a bog"));
+        assert!(matcher.find_iter(b"OG").is_empty());
+    }
+
+    #[test]
+    fn ignore_case_spans_map_back_to_the_original_line() {
+        // 'İ' is two bytes but lowercases to three ("i̇").
+        let matcher = matcher(&["FROG"], true, false, false);
+        let line = "İİ frog Frog";
+
+        let spans = matcher.find_iter(line.as_bytes());
+        assert_eq!(vec![5..9, 10..14], spans);
+        assert_eq!("Frog", &line[spans[1].clone()]);
+    }
+
+    #[test]
+    fn ignore_case_span_covers_whole_characters() {
+        let matcher = matcher(&["i"], true, false, false);
+
+        assert_eq!(vec![0..2], matcher.find_iter("İ".as_bytes()));
+    }
+
+    #[test]
+    fn ignore_case_folds_characters_that_change_length() {
+        let line = "Die Straße, DIE STRASSE";
+
+        assert_eq!(vec![4..11, 17..24], matcher(&["strasse"], true, false, false).find_iter(line.as_bytes()));
+        assert_eq!(vec![4..11, 17..24], matcher(&["STRAẞE"], true, false, false).find_iter(line.as_bytes()));
+        assert_eq!(vec![2..4], matcher(&["s"], true, false, false).find_iter("Maß".as_bytes()));
+    }
+
+    #[test]
+    fn ignore_case_folds_greek_and_turkish() {
+        let sigma = matcher(&["ΟΔΟΣ"], true, false, false);
+        let istanbul = matcher(&["istanbul"], true, false, false);
+
+        assert!(sigma.is_match("οδος".as_bytes()) && sigma.is_match("οδοσ".as_bytes()));
+        assert!(istanbul.is_match("İstanbul".as_bytes()) && istanbul.is_match("ISTANBUL".as_bytes()));
+        assert!(matcher(&["İZMİR"], true, false, false).is_match("izmir".as_bytes()));
+    }
+
+    #[test]
+    fn whole_words() {
+        let line = "Then there's the_end, the end";
+
+        assert_eq!(vec![22..25], anchored("the", false, false, Anchor::Word).find_iter(line.as_bytes()));
+        assert_eq!(vec![0..4, 22..25], anchored("THE\\w?", true, true, Anchor::Word).find_iter(line.as_bytes()));
+        assert_eq!(vec![4..6], anchored("ab", false, false, Anchor::Word).find_iter(b"aab ab"));
+        assert!(anchored("strasse", true, false, Anchor::Word).is_match("die Straße.".as_bytes()));
+        assert!(!anchored("stras", true, false, Anchor::Word).is_match("die Straße.".as_bytes()));
+        assert!(!anchored("frog", false, false, Anchor::Word).is_match("frogé".as_bytes()));
+        // The accent is a combining mark, which `\w` counts as part of the word.
+        for regex in [false, true] {
+            assert!(!anchored("cafe", false, regex, Anchor::Word).is_match("cafe\u{301} au lait".as_bytes()));
+            assert!(anchored("cafe\u{301}", false, regex, Anchor::Word).is_match("cafe\u{301} au lait".as_bytes()));
+        }
+    }
+
+    #[test]
+    fn whole_words_of_regexes() {
+        // The leftmost-first match "a" is not a word here, but "ab" is.
+        assert_eq!(vec![0..2], anchored("a|ab", false, true, Anchor::Word).find_iter(b"ab"));
+        assert!(!anchored("frog", false, true, Anchor::Word).is_match("frogé".as_bytes()));
+        assert_eq!(vec![2..7], anchored("-frog", false, true, Anchor::Word).find_iter(b"a -frog"));
+        assert!(!anchored("-frog", false, true, Anchor::Word).is_match(b"a-frog"));
+    }
+
+    #[test]
+    fn whole_lines() {
+        let literal = anchored("the end", false, false, Anchor::Line);
+        let folded = anchored("THE ENDE", true, false, Anchor::Line);
+        let regex = anchored("a|ab", false, true, Anchor::Line);
+
+        assert!(literal.is_match(b"the end") && !literal.is_match(b"the end.") && !literal.is_match(b"The end"));
+        assert!(folded.is_match("the ende".as_bytes()) && !folded.is_match(b"the ended"));
+        assert!(regex.is_match(b"ab") && !regex.is_match(b"abc"));
+        assert_eq!(vec![0..2], regex.find_iter(b"ab"));
+    }
+
+    #[test]
+    fn replacements_expand_captures_of_a_single_regex() {
+        let regex = matcher(&[r"(?<animal>fr|b)og"], false, true, false);
+        let literals = matcher(&["frog", "bog"], false, false, false);
+        let line = b"a frog in a bog";
+
+        assert_eq!(vec![(2..6, b"[fr]".to_vec()), (12..15, b"[b]".to_vec())], regex.replacements(line, "[$animal]"));
+        assert_eq!(vec![(2..6, b"$1".to_vec()), (12..15, b"$1".to_vec())], literals.replacements(line, "$1"));
+    }
+
+    #[test]
+    fn fuzzy_spans() {
+        let one = fuzzy("frog", false, 1, Anchor::Anywhere);
+
+        assert_eq!(vec![2..6, 13..16], one.find_iter(b"a frog and a fog"));
+        assert_eq!(vec![2..6], one.find_iter(b"a frogs life"));
+        assert_eq!(vec![2..7], one.find_iter(b"a frpog"));
+        assert!(!one.is_match(b"a fig"));
+        assert_eq!(vec![2..6], fuzzy("frg", false, 1, Anchor::Anywhere).find_iter(b"a frog"));
+        assert!(fuzzy("FROG", true, 1, Anchor::Anywhere).is_match(b"a frig"));
+    }
+
+    #[test]
+    fn fuzzy_words_and_lines() {
+        assert!(fuzzy("frog", false, 1, Anchor::Word).is_match(b"a fog."));
+        assert!(!fuzzy("frog", false, 1, Anchor::Word).is_match(b"a frogging"));
+        // The closest text, "og", is not a word, but "frog" is.
+        assert_eq!(vec![2..6], fuzzy("fog", false, 1, Anchor::Word).find_iter(b"a frog"));
+        assert_eq!(vec![2..6, 7..10], fuzzy("frog", false, 1, Anchor::Word).find_iter(b"a frog fog frogging"));
+        assert!(fuzzy("frog", false, 2, Anchor::Line).is_match(b"froggy"));
+        assert!(!fuzzy("frog", false, 2, Anchor::Line).is_match(b"a froggy"));
+    }
+
+    #[test]
+    fn fuzzy_distances() {
+        assert_eq!(Some(0), fuzzy("frog", false, 1, Anchor::Anywhere).distance(b"a frog in a bog"));
+        assert_eq!(Some(2), fuzzy("frog", false, 1, Anchor::Anywhere).distance(b"a fig"));
+        assert_eq!(Some(1), fuzzy("FROG", true, 1, Anchor::Anywhere).distance(b"a FOG"));
+        assert_eq!(Some(0), fuzzy("STRASSE", true, 0, Anchor::Anywhere).distance("die Straße".as_bytes()));
+        assert_eq!(Some(1), fuzzy("fog", false, 1, Anchor::Word).distance(b"a frog"));
+        assert_eq!(Some(2), fuzzy("frog", false, 1, Anchor::Line).distance(b"a frog"));
+        assert_eq!(None, matcher(&["frog"], false, false, false).distance(b"a frog"));
+    }
+
+    #[test]
+    fn regex_spans_skip_empty_matches() {
+        let matcher = matcher(&["o*"], false, true, false);
+
+        assert_eq!(vec![1..3], matcher.find_iter(b"fooa"));
+    }
+
+    #[test]
+    fn any_or_all_patterns() {
+        let any = matcher(&["frog", "bog"], false, false, false);
+        let all = matcher(&["frog", "bog"], false, false, true);
+
+        assert!(any.is_match(b"a bog") && any.is_match(b"a frog"));
+        assert!(!all.is_match(b"a bog") && all.is_match(b"a frog in a bog"));
+        assert!(!matcher(&[], false, false, false).is_match(b"frog"));
+        assert!(!matcher(&[], false, false, true).is_match(b"frog"));
+    }
+
+    #[test]
+    fn required_literal() {
+        assert_eq!(Some("frog"), matcher(&["frog"], false, false, false).required_literal());
+        assert_eq!(Some("frog"), matcher(&["frog", "bog"], false, false, true).required_literal());
+        assert_eq!(None, matcher(&["frog", "bog"], false, false, false).required_literal());
+        assert_eq!(None, matcher(&["frog"], true, false, false).required_literal());
+        assert_eq!(None, matcher(&["frog"], false, true, false).required_literal());
+    }
+
+    #[test]
+    fn spans_of_several_patterns_are_merged() {
+        let matcher = matcher(&["bog", "og", "a b"], false, false, false);
+
+        assert_eq!(vec![2..4, 5..10], matcher.find_iter(b"frog a bog"));
+    }
+
+    #[test]
+    fn invalid_utf8_is_matched_around() {
+        let line = b"\xff frog \xfe\xfd FROG";
+
+        assert_eq!(vec![2..6], matcher(&["frog"], false, false, false).find_iter(line));
+        assert_eq!(vec![2..6, 10..14], matcher(&["frog"], true, false, false).find_iter(line));
+        assert_eq!(vec![2..6, 10..14], matcher(&["(?i)f.og"], false, true, false).find_iter(line));
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs::File;
+
+use memmap2::Mmap;
+
+use crate::config::Config;
+
+/// Files at least this large are mapped unless `--no-mmap` is given.
+const THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Maps `file` into memory when `--mmap` is given or it is at least
+/// [`THRESHOLD`] bytes, so it is searched in place rather than copied into
+/// buffers. Returns `None` when it is to be read instead: with `--no-mmap`,
+/// for pipes, devices and empty files, and when mapping fails.
+pub(crate) fn map(config: &Config, file: &File) -> Option<Mmap> {
+    let metadata = file.metadata().ok()?;
+    let wanted = config.mmap.unwrap_or(metadata.len() >= THRESHOLD);
+    if !wanted || !metadata.is_file() || metadata.len() == 0 {
+        return None;
+    }
+
+    // SAFETY: the map is only read while the file is searched. Like grep
+    // and ripgrep, this accepts that another process truncating the file at
+    // the same time makes reading past the new end fail (with SIGBUS).
+    let map = unsafe { Mmap::map(file) }.ok()?;
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential).ok();
+    Some(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::io::Write;
+
+    #[test]
+    fn maps_regular_files_as_asked() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"a frog\n").unwrap();
+
+        let mapped = map(&test_config(&["minigrep", "--mmap", "frog"]), &file);
+        assert_eq!(Some(&b"a frog\n"[..]), mapped.as_deref());
+        assert!(map(&test_config(&["minigrep", "frog"]), &file).is_none());
+        assert!(map(&test_config(&["minigrep", "--mmap", "--no-mmap", "frog"]), &file).is_none());
+
+        #[cfg(unix)]
+        assert!(map(&test_config(&["minigrep", "--mmap", "frog"]), &File::open("/dev/null").unwrap()).is_none());
+    }
+}
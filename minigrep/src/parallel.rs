@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::config::Config;
+use crate::error::SearchError;
+use crate::printer::{FileOutput, Printer};
+use crate::search_path;
+
+/// Searches `files` on `threads` worker threads.
+///
+/// Each worker renders a whole file into memory; the calling thread then
+/// prints the files strictly in the order given, so the output is the same
+/// as a serial search, just sooner.
+pub(crate) fn search_files<W: Write>(
+    config: &Config,
+    is_match: &(dyn Fn(&[u8]) -> bool + Sync),
+    printer: &mut Printer<W>,
+    files: &[PathBuf],
+    threads: usize,
+) -> io::Result<()> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let color = printer.color();
+    let matcher = printer.matcher();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<(usize, Result<FileOutput, SearchError>)>();
+        for _ in 0..threads.min(files.len()) {
+            let sender = sender.clone();
+            let (next, stop) = (&next, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = files.get(index) else { break };
+
+                    let mut file_printer = Printer::new(config, matcher, Vec::new(), color);
+                    file_printer.show_names = true;
+                    let output = search_path(config, is_match, &mut file_printer, path, true)
+                        .map(|()| file_printer.into_output());
+                    if sender.send((index, output)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Files finishing early wait here until everything before them is printed.
+        let mut finished = BTreeMap::new();
+        let mut next_to_print = 0;
+        let print_in_order = || -> io::Result<()> {
+            for (index, output) in receiver {
+                finished.insert(index, output);
+                while let Some(output) = finished.remove(&next_to_print) {
+                    match output {
+                        Ok(output) => printer.append(output)?,
+                        Err(e) => printer.skip_file(&files[next_to_print], e)?,
+                    }
+                    if printer.done() {
+                        return Ok(());
+                    }
+                    next_to_print += 1;
+                }
+            }
+            Ok(())
+        };
+
+        // Workers finish the file at hand but take no more once printing stops.
+        let result = print_in_order();
+        stop.store(true, Ordering::Relaxed);
+        result
+    })
+}
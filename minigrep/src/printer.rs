@@ -0,0 +1,493 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::error::SearchError;
+use crate::matcher::Matcher;
+use crate::stream::Line;
+
+// ANSI escapes for the parts of the output that get colored, as in grep.
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const PATH_COLOR: &str = "\x1b[35m";
+const NUMBER_COLOR: &str = "\x1b[32m";
+const SEPARATOR_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Writes search results in the format selected by the command line.
+pub(crate) struct Printer<'a, W> {
+    config: &'a Config,
+    matcher: &'a Matcher,
+    out: W,
+    color: bool,
+    /// Set when several files are searched, so every line names its file.
+    pub(crate) show_names: bool,
+    /// Whether a group of lines has been printed, so that the next group
+    /// (possibly from another file) needs a `--` separator when printing context.
+    printed_group: bool,
+    stats: Stats,
+    /// Files that could not be searched, see [`Printer::skip_file`].
+    skipped: usize,
+}
+
+/// Totals reported by the summary record of `--json`.
+#[derive(Default)]
+struct Stats {
+    files: usize,
+    files_with_matches: usize,
+    matched_lines: usize,
+    submatches: usize,
+}
+
+impl Stats {
+    fn add(&mut self, other: &Stats) {
+        self.files += other.files;
+        self.files_with_matches += other.files_with_matches;
+        self.matched_lines += other.matched_lines;
+        self.submatches += other.submatches;
+    }
+}
+
+/// What a [`Printer`] writing to memory produced for one file, ready to be
+/// added to the real output with [`Printer::append`].
+pub(crate) struct FileOutput {
+    bytes: Vec<u8>,
+    stats: Stats,
+    printed_group: bool,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    pub(crate) fn new(config: &'a Config, matcher: &'a Matcher, out: W, color: bool) -> Self {
+        Printer {
+            config,
+            matcher,
+            out,
+            color: color && !config.json,
+            show_names: false,
+            printed_group: false,
+            stats: Stats::default(),
+            skipped: 0,
+        }
+    }
+
+    pub(crate) fn color(&self) -> bool {
+        self.color
+    }
+
+    pub(crate) fn matcher(&self) -> &'a Matcher {
+        self.matcher
+    }
+
+    /// Whether any file had a selected line (or, with `--write`, a
+    /// replacement), or with `-L` whether any file was listed.
+    pub(crate) fn matched(&self) -> bool {
+        match self.config.files_without_match {
+            true => self.stats.files > self.stats.files_with_matches,
+            false => self.stats.files_with_matches > 0,
+        }
+    }
+
+    /// Whether searching more files could change neither the output nor the
+    /// exit status, as after the first selected line with `-q`.
+    pub(crate) fn done(&self) -> bool {
+        self.config.quiet && self.matched()
+    }
+
+    /// With -l, -L and -q, all that matters about a file is whether it has a
+    /// selected line, so it is read no further than the first one.
+    fn stops_at_first_match(&self) -> bool {
+        self.config.files_with_matches || self.config.files_without_match || self.config.quiet
+    }
+
+    /// How many files could not be searched.
+    pub(crate) fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Decides what a failure to search one file of a tree means for the rest
+    /// of the search: failing to write the results stops it. A file that
+    /// cannot be read is reported and counted, and the search goes on.
+    pub(crate) fn skip_file(&mut self, path: &Path, e: SearchError) -> io::Result<()> {
+        let e = match e {
+            SearchError::Read(e) => e,
+            SearchError::Write(e) => return Err(e),
+        };
+        eprintln!("minigrep: {}: {e}", path.display());
+        self.skipped += 1;
+        Ok(())
+    }
+
+    /// Adds the output of a file printed elsewhere, as if this printer had
+    /// printed it itself.
+    pub(crate) fn append(&mut self, output: FileOutput) -> io::Result<()> {
+        let has_context = self.config.before_context > 0 || self.config.after_context > 0;
+        if has_context && output.printed_group && self.printed_group {
+            self.write_colored(SEPARATOR_COLOR, "--")?;
+            writeln!(self.out)?;
+        }
+        self.printed_group |= output.printed_group;
+        self.stats.add(&output.stats);
+        self.out.write_all(&output.bytes)
+    }
+
+    /// Prints the selected lines of `file`.
+    pub(crate) fn print_file(
+        &mut self,
+        file: &Path,
+        lines: impl Iterator<Item = io::Result<Line>>,
+    ) -> Result<(), SearchError> {
+        if self.stops_at_first_match() {
+            self.print_name(file, lines)?;
+        } else if self.ranks_lines() {
+            let lines = self.ranked(lines)?;
+            self.print_lines(file, lines.into_iter().map(Ok))?;
+        } else {
+            self.print_lines(file, lines)?;
+        }
+        self.stats.files += 1;
+        Ok(())
+    }
+
+    fn print_lines(&mut self, file: &Path, lines: impl Iterator<Item = io::Result<Line>>) -> Result<(), SearchError> {
+        match self.config.json {
+            true => self.print_json(file, lines),
+            false => self.print_text(file, lines),
+        }
+    }
+
+    /// Whether the lines of a file are printed closest first, as they are
+    /// for `--fuzzy`. Context lines belong next to their matches and lines
+    /// selected by -v match nothing, so then the order is kept.
+    fn ranks_lines(&self) -> bool {
+        let has_context = self.config.before_context > 0 || self.config.after_context > 0;
+        self.config.fuzzy.is_some() && !self.config.invert_match && !has_context && !self.config.count
+    }
+
+    /// Reads all of `lines` and sorts them by how close they come to the
+    /// queries, keeping the order of lines equally close.
+    fn ranked(&self, lines: impl Iterator<Item = io::Result<Line>>) -> Result<Vec<Line>, SearchError> {
+        let mut lines = lines.collect::<io::Result<Vec<Line>>>().map_err(SearchError::Read)?;
+        lines.sort_by_cached_key(|line| match line {
+            Line::Match { text, .. } => self.matcher.distance(text),
+            _ => None,
+        });
+        Ok(lines)
+    }
+
+    fn print_text(&mut self, file: &Path, lines: impl Iterator<Item = io::Result<Line>>) -> Result<(), SearchError> {
+        let name = self.show_names.then(|| file.display().to_string());
+
+        if self.config.count {
+            let mut count = 0;
+            for line in lines {
+                count += usize::from(matches!(line.map_err(SearchError::Read)?, Line::Match { .. }));
+            }
+            self.stats.files_with_matches += usize::from(count > 0);
+            if let Some(name) = &name {
+                self.write_colored(PATH_COLOR, name)?;
+                self.write_colored(SEPARATOR_COLOR, ":")?;
+            }
+            writeln!(self.out, "{count}")?;
+            return Ok(());
+        }
+
+        let has_context = self.config.before_context > 0 || self.config.after_context > 0;
+        let mut first = true;
+        let mut matched = false;
+        for line in lines {
+            let (separator, number, text, selected) = match line.map_err(SearchError::Read)? {
+                Line::Match { number, text, .. } => {
+                    matched = true;
+                    (":", number, text, true)
+                }
+                Line::Context { number, text, .. } => ("-", number, text, false),
+                Line::Separator => {
+                    self.write_colored(SEPARATOR_COLOR, "--")?;
+                    writeln!(self.out)?;
+                    continue;
+                }
+            };
+            if first && has_context && self.printed_group {
+                self.write_colored(SEPARATOR_COLOR, "--")?;
+                writeln!(self.out)?;
+            }
+            first = false;
+            self.printed_group = true;
+
+            if let Some(name) = &name {
+                self.write_colored(PATH_COLOR, name)?;
+                self.write_colored(SEPARATOR_COLOR, separator)?;
+            }
+            if name.is_some() || self.config.line_number {
+                self.write_colored(NUMBER_COLOR, number.to_string())?;
+                self.write_colored(SEPARATOR_COLOR, separator)?;
+            }
+            // Lines selected by -v contain no match to highlight.
+            let has_matches = selected && !self.config.invert_match;
+            if let (true, Some(replacement)) = (has_matches, &self.config.replace) {
+                self.write_replaced(&text, replacement)?;
+            } else if self.color && has_matches {
+                self.write_highlighted(&text)?;
+            } else {
+                self.out.write_all(&text)?;
+            }
+            writeln!(self.out)?;
+        }
+
+        self.stats.files_with_matches += usize::from(matched);
+        Ok(())
+    }
+
+    /// Reports that a binary file has a selected line instead of printing the
+    /// lines themselves, which would garble a terminal. Counts are unaffected.
+    pub(crate) fn print_binary(
+        &mut self,
+        file: &Path,
+        lines: impl Iterator<Item = io::Result<Line>>,
+    ) -> Result<(), SearchError> {
+        if self.config.count || self.stops_at_first_match() {
+            return self.print_file(file, lines);
+        }
+
+        if selects_any(lines)? {
+            self.stats.files_with_matches += 1;
+            if self.config.json {
+                writeln!(self.out, "{}", json!({ "type": "binary", "file": file.display().to_string() }))?;
+            } else {
+                writeln!(self.out, "Binary file {} matches", file.display())?;
+            }
+        }
+        self.stats.files += 1;
+        Ok(())
+    }
+
+    /// Prints the name of `file` if it is to be listed by -l or -L, and
+    /// nothing with -q.
+    fn print_name(&mut self, file: &Path, lines: impl Iterator<Item = io::Result<Line>>) -> Result<(), SearchError> {
+        let matched = selects_any(lines)?;
+        self.stats.files_with_matches += usize::from(matched);
+        if self.config.quiet || matched == self.config.files_without_match {
+            return Ok(());
+        }
+        self.write_colored(PATH_COLOR, file.display().to_string())?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    /// Reports that `file` was rewritten with `replacements` matches replaced.
+    /// Files where nothing was replaced are not mentioned.
+    pub(crate) fn print_rewritten(&mut self, file: &Path, replacements: usize) -> io::Result<()> {
+        self.stats.files += 1;
+        if replacements == 0 {
+            return Ok(());
+        }
+        self.stats.files_with_matches += 1;
+        self.stats.submatches += replacements;
+
+        if self.config.json {
+            let record = json!({ "type": "rewrite", "file": file.display().to_string(), "replacements": replacements });
+            return writeln!(self.out, "{record}");
+        }
+        self.write_colored(PATH_COLOR, file.display().to_string())?;
+        self.write_colored(SEPARATOR_COLOR, ":")?;
+        let plural = if replacements == 1 { "" } else { "s" };
+        writeln!(self.out, " {replacements} replacement{plural}")
+    }
+
+    /// Writes one JSON object per selected or context line. Lines that are
+    /// not valid UTF-8 are shown lossily; offsets always refer to the bytes.
+    fn print_json(&mut self, file: &Path, lines: impl Iterator<Item = io::Result<Line>>) -> Result<(), SearchError> {
+        let path = file.display().to_string();
+        let mut matched = false;
+
+        for line in lines {
+            let record = match line.map_err(SearchError::Read)? {
+                Line::Match { number, offset, text } => {
+                    let spans = match self.config.invert_match {
+                        true => Vec::new(),
+                        false => self.matcher.find_iter(&text),
+                    };
+                    matched = true;
+                    self.stats.matched_lines += 1;
+                    self.stats.submatches += spans.len();
+
+                    let submatches: Vec<_> = spans
+                        .into_iter()
+                        .map(|span| {
+                            let found = String::from_utf8_lossy(&text[span.clone()]);
+                            json!({ "match": found, "start": span.start, "end": span.end })
+                        })
+                        .collect();
+                    json!({
+                        "type": "match",
+                        "file": path,
+                        "line_number": number,
+                        "byte_offset": offset,
+                        "line": String::from_utf8_lossy(&text),
+                        "submatches": submatches,
+                    })
+                }
+                Line::Context { number, offset, text } => json!({
+                    "type": "context",
+                    "file": path,
+                    "line_number": number,
+                    "byte_offset": offset,
+                    "line": String::from_utf8_lossy(&text),
+                }),
+                Line::Separator => continue,
+            };
+            writeln!(self.out, "{record}")?;
+        }
+
+        self.stats.files_with_matches += usize::from(matched);
+        Ok(())
+    }
+
+    /// Sends what has been printed so far on its way.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Writes anything that belongs at the very end of the output and flushes it.
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        if self.config.json && !self.config.quiet {
+            let summary = json!({
+                "type": "summary",
+                "files": self.stats.files,
+                "files_with_matches": self.stats.files_with_matches,
+                "matched_lines": self.stats.matched_lines,
+                "submatches": self.stats.submatches,
+            });
+            writeln!(self.out, "{summary}")?;
+        }
+        self.out.flush()
+    }
+
+    fn write_colored(&mut self, color: &str, text: impl AsRef<[u8]>) -> io::Result<()> {
+        if self.color {
+            self.out.write_all(color.as_bytes())?;
+            self.out.write_all(text.as_ref())?;
+            self.out.write_all(RESET.as_bytes())
+        } else {
+            self.out.write_all(text.as_ref())
+        }
+    }
+
+    fn write_highlighted(&mut self, line: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+        for span in self.matcher.find_iter(line) {
+            self.out.write_all(&line[written..span.start])?;
+            self.write_colored(MATCH_COLOR, &line[span.clone()])?;
+            written = span.end;
+        }
+        self.out.write_all(&line[written..])
+    }
+
+    fn write_replaced(&mut self, line: &[u8], replacement: &str) -> io::Result<()> {
+        let mut written = 0;
+        for (span, replaced) in self.matcher.replacements(line, replacement) {
+            self.out.write_all(&line[written..span.start])?;
+            self.write_colored(MATCH_COLOR, replaced)?;
+            written = span.end;
+        }
+        self.out.write_all(&line[written..])
+    }
+}
+
+impl Printer<'_, Vec<u8>> {
+    pub(crate) fn into_output(self) -> FileOutput {
+        FileOutput { bytes: self.out, stats: self.stats, printed_group: self.printed_group }
+    }
+}
+
+/// Reads `lines` up to the first selected one, if there is one.
+fn selects_any(lines: impl Iterator<Item = io::Result<Line>>) -> Result<bool, SearchError> {
+    for line in lines {
+        if let Line::Match { .. } = line.map_err(SearchError::Read)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::stream::search_reader_with_context;
+
+    fn json_output(args: &[&str], input: &str) -> Vec<serde_json::Value> {
+        let config = test_config(args);
+        let matcher =
+            Matcher::new(&config.patterns, config.ignore_case, config.syntax(), config.all_patterns, config.anchor())
+                .unwrap();
+
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out, false);
+        let lines = search_reader_with_context(input.as_bytes(), |line| matcher.is_match(line), 0, 1);
+        printer.print_file(Path::new("poem.txt"), lines).unwrap();
+        printer.finish().unwrap();
+
+        String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn json_records_of_invalid_utf8_keep_byte_offsets() {
+        let config = test_config(&["minigrep", "--json", "og"]);
+        let matcher = Matcher::new(&config.patterns, false, config.syntax(), false, config.anchor()).unwrap();
+
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out, false);
+        let lines = search_reader_with_context(&b"\xff frog\n"[..], |line| matcher.is_match(line), 0, 0);
+        printer.print_file(Path::new("frog.bin"), lines).unwrap();
+
+        let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json!("\u{fffd} frog"), record["line"]);
+        assert_eq!(json!([{ "match": "og", "start": 4, "end": 6 }]), record["submatches"]);
+    }
+
+    #[test]
+    fn json_records() {
+        let records = json_output(&["minigrep", "--json", "og"], "How public,\nlike a frog in a bog\nthe end\n");
+
+        assert_eq!(
+            vec![
+                json!({
+                    "type": "match",
+                    "file": "poem.txt",
+                    "line_number": 2,
+                    "byte_offset": 12,
+                    "line": "like a frog in a bog",
+                    "submatches": [
+                        { "match": "og", "start": 9, "end": 11 },
+                        { "match": "og", "start": 18, "end": 20 },
+                    ],
+                }),
+                json!({ "type": "context", "file": "poem.txt", "line_number": 3, "byte_offset": 33, "line": "the end" }),
+                json!({ "type": "summary", "files": 1, "files_with_matches": 1, "matched_lines": 1, "submatches": 2 }),
+            ],
+            records
+        );
+    }
+
+    #[test]
+    fn matches_and_skipped_files_are_counted() {
+        let config = test_config(&["minigrep", "frog"]);
+        let matcher = Matcher::new(&config.patterns, false, config.syntax(), false, config.anchor()).unwrap();
+        let mut printer = Printer::new(&config, &matcher, Vec::new(), false);
+
+        let lines = search_reader_with_context(&b"a bog\n"[..], |line| matcher.is_match(line), 0, 0);
+        printer.print_file(Path::new("bog.txt"), lines).unwrap();
+        assert!(!printer.matched());
+        let lines = search_reader_with_context(&b"a frog\n"[..], |line| matcher.is_match(line), 0, 0);
+        printer.print_file(Path::new("frog.txt"), lines).unwrap();
+        assert!(printer.matched());
+
+        let denied = SearchError::Read(io::Error::from(io::ErrorKind::PermissionDenied));
+        printer.skip_file(Path::new("secret.txt"), denied).unwrap();
+        let closed = SearchError::Write(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(printer.skip_file(Path::new("frog.txt"), closed).is_err());
+        assert_eq!(1, printer.skipped());
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use tempfile::NamedTempFile;
+
+use crate::config::Config;
+use crate::matcher::Matcher;
+use crate::stream::{is_binary, trim_newline};
+
+/// Replaces every match in the file at `path` with `replacement` and returns
+/// how many were replaced, or `None` for a binary file, which is left alone
+/// unless `--text` is given.
+///
+/// The new contents are written to a temporary file next to the original,
+/// which then takes its place, so readers see either the old file or the new
+/// one and never half of each. Line endings and permissions are kept, and a
+/// file without matches is not touched at all.
+pub(crate) fn rewrite_file(
+    config: &Config,
+    matcher: &Matcher,
+    path: &Path,
+    replacement: &str,
+) -> io::Result<Option<usize>> {
+    let contents = fs::read(path)?;
+    if !config.text && is_binary(&mut &contents[..])? {
+        return Ok(None);
+    }
+
+    let mut rewritten = Vec::with_capacity(contents.len());
+    let mut count = 0;
+    for chunk in contents.split_inclusive(|&byte| byte == b'\n') {
+        let line = trim_newline(chunk);
+        if !matcher.is_match(line) {
+            rewritten.extend_from_slice(chunk);
+            continue;
+        }
+
+        let mut written = 0;
+        for (span, replaced) in matcher.replacements(line, replacement) {
+            rewritten.extend_from_slice(&line[written..span.start]);
+            rewritten.extend_from_slice(&replaced);
+            written = span.end;
+            count += 1;
+        }
+        rewritten.extend_from_slice(&chunk[written..]);
+    }
+    if count == 0 {
+        return Ok(Some(0));
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp = NamedTempFile::new_in(dir)?;
+    temp.write_all(&rewritten)?;
+    temp.as_file().set_permissions(fs::metadata(path)?.permissions())?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| e.error)?;
+
+    Ok(Some(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::matcher::{Anchor, Syntax};
+
+    #[test]
+    fn rewrites_matches_keeping_line_endings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("poem.txt");
+        fs::write(&path, "a frog\r\nno match\nfrog and frog").unwrap();
+        let config = test_config(&["minigrep", "--replace=toad", "frog"]);
+        let matcher = Matcher::new(&config.patterns, false, Syntax::Literal, false, Anchor::Anywhere).unwrap();
+
+        let replaced = rewrite_file(&config, &matcher, &path, "toad").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(Some(3), replaced);
+        assert_eq!("a toad\r\nno match\ntoad and toad", contents);
+    }
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::config::{color_choice, Config};
+use crate::error::Error;
+
+/// The user's configuration file: `$XDG_CONFIG_HOME/minigrep/config.toml`,
+/// or `~/.config/minigrep/config.toml` when that is not set.
+pub(crate) fn user_file() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    let config_home = config_home.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("minigrep").join("config.toml"))
+}
+
+/// The project's configuration file: the nearest `.minigreprc` in the
+/// current directory or one of its parents.
+pub(crate) fn project_file() -> Option<PathBuf> {
+    let current = env::current_dir().ok()?;
+    current.ancestors().map(|dir| dir.join(".minigreprc")).find(|path| path.is_file())
+}
+
+/// Sets the options found in the configuration file at `path` on `config`.
+/// A missing file sets nothing.
+pub(crate) fn apply_file(config: &mut Config, path: &Path) -> Result<(), Error> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(source) => return Err(Error::Io { path: path.to_path_buf(), source }),
+    };
+    apply(config, &contents).map_err(|message| Error::Settings { path: path.to_path_buf(), message })
+}
+
+/// Sets the options in the TOML `contents` on `config`. Keys are named after
+/// the long options they stand for.
+fn apply(config: &mut Config, contents: &str) -> Result<(), String> {
+    let table: Table = contents.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+
+    // As on the command line, before-context and after-context win over context.
+    if let Some(value) = table.get("context") {
+        config.before_context = number("context", value)?;
+        config.after_context = config.before_context;
+    }
+    for (key, value) in &table {
+        match key.as_str() {
+            "context" => {}
+            "before-context" => config.before_context = number(key, value)?,
+            "after-context" => config.after_context = number(key, value)?,
+            "color" => config.color = color_choice(string(key, value)?)?,
+            "ignore-case" => config.ignore_case = boolean(key, value)?,
+            "line-number" => config.line_number = boolean(key, value)?,
+            "hidden" => config.hidden = boolean(key, value)?,
+            "no-ignore" => config.no_ignore = boolean(key, value)?,
+            "text" => config.text = boolean(key, value)?,
+            "search-zip" => config.search_zip = boolean(key, value)?,
+            "threads" => match number(key, value)? {
+                0 => return Err("invalid thread count '0'".to_string()),
+                threads => config.threads = Some(threads),
+            },
+            "mmap" => config.mmap = Some(boolean(key, value)?),
+            "include" => config.include.extend(strings(key, value)?),
+            "exclude" => config.exclude.extend(strings(key, value)?),
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+    Ok(())
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("'{key}' must be true or false"))
+}
+
+fn number(key: &str, value: &Value) -> Result<usize, String> {
+    let number = value.as_integer().and_then(|number| usize::try_from(number).ok());
+    number.ok_or_else(|| format!("'{key}' must be a number, 0 or more"))
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    value.as_str().ok_or_else(|| format!("'{key}' must be a string"))
+}
+
+/// A list of strings, or a single one.
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(values) => values.iter().map(|value| string(key, value).map(String::from)).collect(),
+        value => Ok(vec![string(key, value)?.to_string()]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColorChoice;
+
+    #[test]
+    fn settings_from_toml() {
+        let mut config = Config::default();
+        let contents = r#"
+            color = "always"
+            ignore-case = true
+            after-context = 1
+            context = 3
+            exclude = ["target", "*.log"]
+            include = "*.rs"
+        "#;
+        apply(&mut config, contents).unwrap();
+
+        assert_eq!(ColorChoice::Always, config.color);
+        assert!(config.ignore_case && !config.line_number);
+        assert_eq!((3, 1), (config.before_context, config.after_context));
+        assert_eq!(vec!["*.rs"], config.include);
+        assert_eq!(vec!["target", "*.log"], config.exclude);
+    }
+
+    #[test]
+    fn later_files_override_earlier_ones() {
+        let mut config = Config::default();
+        apply(&mut config, "ignore-case = true\nexclude = \"target\"").unwrap();
+        apply(&mut config, "ignore-case = false\nexclude = \"*.log\"").unwrap();
+
+        assert!(!config.ignore_case);
+        assert_eq!(vec!["target", "*.log"], config.exclude);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let mut config = Config::default();
+
+        assert_eq!(Err("unknown setting 'colour'".to_string()), apply(&mut config, "colour = \"never\""));
+        assert_eq!(Err("'context' must be a number, 0 or more".to_string()), apply(&mut config, "context = -1"));
+        assert_eq!(Err("'hidden' must be true or false".to_string()), apply(&mut config, "hidden = \"yes\""));
+        assert!(apply(&mut config, "color = \"sometimes\"").is_err());
+        assert!(apply(&mut config, "color = ").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+use memchr::{memchr_iter, memmem, memrchr, memrchr_iter};
+
+/// Iterator over the lines of a reader that satisfy a predicate, yielding
+/// each one with its 1-based line number.
+///
+/// Only one line is held in memory at a time and only matching lines are
+/// copied out, so inputs of any size can be searched. Lines are bytes, so
+/// input that is not valid UTF-8 is searched rather than rejected.
+pub struct Matches<R, F> {
+    reader: R,
+    is_match: F,
+    line: Vec<u8>,
+    number: usize,
+}
+
+/// Searches `reader` line by line, see [`Matches`].
+pub fn search_reader<R, F>(reader: R, is_match: F) -> Matches<R, F>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> bool,
+{
+    Matches { reader, is_match, line: Vec::new(), number: 0 }
+}
+
+impl<R, F> Iterator for Matches<R, F>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> bool,
+{
+    type Item = io::Result<(usize, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            self.number += 1;
+
+            let line = trim_newline(&self.line);
+            if (self.is_match)(line) {
+                return Some(Ok((self.number, line.to_vec())));
+            }
+        }
+    }
+}
+
+/// A line produced by [`ContextMatches`]. `number` is 1-based and `offset`
+/// is the position in bytes of the start of the line within the input.
+#[derive(Debug, PartialEq)]
+pub enum Line {
+    /// A selected line.
+    Match { number: usize, offset: u64, text: Vec<u8> },
+    /// A line printed only because it is near a selected one.
+    Context { number: usize, offset: u64, text: Vec<u8> },
+    /// A gap between two groups of lines that are not adjacent.
+    Separator,
+}
+
+/// Like [`Matches`], but also yields up to `before` lines preceding and
+/// `after` lines following each match, with a [`Line::Separator`] between
+/// groups that are not adjacent. Only the last `before` lines are buffered.
+pub struct ContextMatches<R, F> {
+    reader: R,
+    is_match: F,
+    line: Vec<u8>,
+    number: usize,
+    offset: u64,
+    before: usize,
+    after: usize,
+    history: VecDeque<(usize, u64, Vec<u8>)>,
+    after_left: usize,
+    last_emitted: Option<usize>,
+    pending: VecDeque<Line>,
+    /// How many more lines may be selected, see [`ContextMatches::max_count`].
+    matches_left: Option<usize>,
+    /// Text every selected line contains, see [`ContextMatches::skip_lines_without`].
+    required: Option<memmem::Finder<'static>>,
+}
+
+/// Searches `reader` line by line with surrounding context, see [`ContextMatches`].
+pub fn search_reader_with_context<R, F>(reader: R, is_match: F, before: usize, after: usize) -> ContextMatches<R, F>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> bool,
+{
+    ContextMatches {
+        reader,
+        is_match,
+        line: Vec::new(),
+        number: 0,
+        offset: 0,
+        before,
+        after,
+        history: VecDeque::new(),
+        after_left: 0,
+        last_emitted: None,
+        pending: VecDeque::new(),
+        matches_left: None,
+        required: None,
+    }
+}
+
+impl<R, F> ContextMatches<R, F> {
+    /// Stops after `max` selected lines and the context following the last
+    /// of them, without reading any further, as `grep -m` does.
+    pub fn max_count(mut self, max: usize) -> Self {
+        self.matches_left = Some(max);
+        self
+    }
+
+    /// Declares that every selected line contains `needle`, so that lines
+    /// without it need not be matched one by one: the buffered input is
+    /// scanned for `needle` with a vectorized substring search instead, and
+    /// only the line around each hit is matched.
+    pub fn skip_lines_without(mut self, needle: &[u8]) -> Self {
+        self.required = Some(memmem::Finder::new(needle).into_owned());
+        self
+    }
+}
+
+impl<R: BufRead, F> ContextMatches<R, F> {
+    /// Consumes the buffered lines before the next one containing the
+    /// required text, keeping count of them and remembering the last few as
+    /// context. Stops at such a line, at a line longer than the buffer, or at
+    /// the end of the input.
+    fn skip_to_candidate(&mut self) -> io::Result<()> {
+        let Some(required) = &self.required else { return Ok(()) };
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let hit = required.find(buffer);
+            // Without a hit, the last line may continue past the buffer.
+            let end = memrchr(b'\n', &buffer[..hit.unwrap_or(buffer.len())]).map_or(0, |newline| newline + 1);
+            if end == 0 {
+                return Ok(());
+            }
+
+            let skipped = &buffer[..end];
+            // Only the lines that may be needed as context are split off.
+            let kept = match self.before {
+                0 => end,
+                before => memrchr_iter(b'\n', &skipped[..end - 1]).nth(before - 1).map_or(0, |newline| newline + 1),
+            };
+            self.number += memchr_iter(b'\n', &skipped[..kept]).count();
+            self.offset += kept as u64;
+            for line in skipped[kept..].split_inclusive(|&byte| byte == b'\n') {
+                self.number += 1;
+                if self.history.len() == self.before {
+                    self.history.pop_front();
+                }
+                self.history.push_back((self.number, self.offset, trim_newline(line).to_vec()));
+                self.offset += line.len() as u64;
+            }
+            self.reader.consume(end);
+
+            if hit.is_some() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<R, F> Iterator for ContextMatches<R, F>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> bool,
+{
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Some(Ok(line));
+            }
+            if self.matches_left == Some(0) && self.after_left == 0 {
+                return None;
+            }
+            // Lines that would be printed as after context are read one by one.
+            if self.after_left == 0
+                && let Err(e) = self.skip_to_candidate()
+            {
+                return Some(Err(e));
+            }
+
+            self.line.clear();
+            let offset = self.offset;
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(read) => self.offset += read as u64,
+                Err(e) => return Some(Err(e)),
+            }
+            self.number += 1;
+            let number = self.number;
+
+            let line = trim_newline(&self.line);
+            if (self.is_match)(line) {
+                match &mut self.matches_left {
+                    // The after context of the last selected line ends at the next one.
+                    Some(0) => {
+                        self.after_left = 0;
+                        return None;
+                    }
+                    Some(left) => *left -= 1,
+                    None => {}
+                }
+                let first = number - self.history.len();
+                let has_context = self.before > 0 || self.after > 0;
+                if self.last_emitted.is_some_and(|last| has_context && first > last + 1) {
+                    self.pending.push_back(Line::Separator);
+                }
+                let history = self
+                    .history
+                    .drain(..)
+                    .map(|(number, offset, text)| Line::Context { number, offset, text });
+                self.pending.extend(history);
+                self.pending.push_back(Line::Match { number, offset, text: line.to_vec() });
+                self.last_emitted = Some(number);
+                self.after_left = self.after;
+            } else if self.after_left > 0 {
+                self.after_left -= 1;
+                self.pending.push_back(Line::Context { number, offset, text: line.to_vec() });
+                self.last_emitted = Some(number);
+            } else if self.before > 0 {
+                if self.history.len() == self.before {
+                    self.history.pop_front();
+                }
+                self.history.push_back((number, offset, line.to_vec()));
+            }
+        }
+    }
+}
+
+/// Guesses whether the input behind `reader` is binary by looking for a NUL
+/// byte in its first buffer, or the first 8 KiB of it, without consuming
+/// anything.
+pub fn is_binary(reader: &mut impl BufRead) -> io::Result<bool> {
+    // A default sized buffer, and no more when the whole input is in memory.
+    let buffer = reader.fill_buf()?;
+    Ok(buffer[..buffer.len().min(8 * 1024)].contains(&0))
+}
+
+/// Strips the same line endings as `str::lines`: "\n" or "\r\n".
+pub(crate) fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_numbered_matches() {
+        let reader = "Rust:\r\nsafe, fast, productive.\nPick three.\nTrust me.".as_bytes();
+
+        let matches: Vec<_> = search_reader(reader, |line| line.ends_with(b"me.") || line.starts_with(b"Ru"))
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(vec![(1, b"Rust:".to_vec()), (4, b"Trust me.".to_vec())], matches);
+    }
+
+    #[test]
+    fn searches_through_invalid_utf8() {
+        let reader = &b"Rust:\n\xff\xfe\nTrust me.\n"[..];
+
+        let numbers: Vec<usize> = search_reader(reader, |_| true).map(|line| line.unwrap().0).collect();
+
+        assert_eq!(vec![1, 2, 3], numbers);
+    }
+
+    fn matched(number: usize, offset: u64, text: &str) -> Line {
+        Line::Match { number, offset, text: text.as_bytes().to_vec() }
+    }
+
+    fn context_line(number: usize, offset: u64, text: &str) -> Line {
+        Line::Context { number, offset, text: text.as_bytes().to_vec() }
+    }
+
+    fn context(before: usize, after: usize) -> Vec<Line> {
+        let reader = "a\nb\nfrog\nc\nd\ne\nf\nfrog\ng\nfrog\n".as_bytes();
+        search_reader_with_context(reader, |line| line == b"frog", before, after)
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn no_context_is_just_matches() {
+        assert_eq!(vec![matched(3, 4, "frog"), matched(8, 17, "frog"), matched(10, 24, "frog")], context(0, 0));
+    }
+
+    #[test]
+    fn before_and_after_context_with_separators() {
+        assert_eq!(
+            vec![
+                context_line(2, 2, "b"),
+                matched(3, 4, "frog"),
+                context_line(4, 9, "c"),
+                Line::Separator,
+                context_line(7, 15, "f"),
+                matched(8, 17, "frog"),
+                context_line(9, 22, "g"),
+                matched(10, 24, "frog"),
+            ],
+            context(1, 1)
+        );
+    }
+
+    #[test]
+    fn adjacent_groups_are_merged() {
+        let lines = context(2, 2);
+
+        assert!(!lines.contains(&Line::Separator));
+        assert_eq!(Some(&context_line(1, 0, "a")), lines.first());
+        assert_eq!(Some(&matched(10, 24, "frog")), lines.last());
+        assert_eq!(10, lines.len());
+    }
+
+    #[test]
+    fn huge_context_is_not_allocated_up_front() {
+        assert_eq!(context(2, 2), context(usize::MAX, usize::MAX));
+    }
+
+    #[test]
+    fn max_count_stops_after_the_context_of_the_last_match() {
+        let reader = "a\nb\nfrog\nc\nd\ne\nf\nfrog\ng\nfrog\n".as_bytes();
+        let lines = |max, after| -> Vec<Line> {
+            let lines = search_reader_with_context(reader, |line| line == b"frog", 1, after).max_count(max);
+            lines.collect::<io::Result<_>>().unwrap()
+        };
+
+        assert_eq!(Vec::<Line>::new(), lines(0, 1));
+        assert_eq!(
+            vec![
+                context_line(2, 2, "b"),
+                matched(3, 4, "frog"),
+                context_line(4, 9, "c"),
+                Line::Separator,
+                context_line(7, 15, "f"),
+                matched(8, 17, "frog"),
+                context_line(9, 22, "g"),
+            ],
+            lines(2, 1)
+        );
+        // The context ends early at the next match.
+        assert_eq!(
+            vec![matched(8, 17, "frog"), context_line(9, 22, "g")],
+            lines(2, 4).into_iter().skip(6).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn skipping_lines_without_the_required_text_changes_nothing() {
+        let input = "a\nb\nfrog\nc\r\nd\ne\nf\na frog and a long line\ng\nfrog\nfro\ng\n";
+        for capacity in [1, 3, 7, 64] {
+            for (before, after) in [(0, 0), (1, 1), (2, 0), (0, 3), (3, 3)] {
+                let lines = |skip: bool| -> Vec<Line> {
+                    let reader = io::BufReader::with_capacity(capacity, input.as_bytes());
+                    let mut lines = search_reader_with_context(reader, |line| line.ends_with(b"frog"), before, after);
+                    if skip {
+                        lines = lines.skip_lines_without(b"frog");
+                    }
+                    lines.collect::<io::Result<_>>().unwrap()
+                };
+                assert_eq!(lines(false), lines(true), "capacity {capacity}, context {before} {after}");
+            }
+        }
+    }
+
+    #[test]
+    fn binary_detection_does_not_consume_input() {
+        let mut text = &b"frog\nbog\n"[..];
+        let mut binary = &b"frog\0bog\n"[..];
+
+        assert!(!is_binary(&mut text).unwrap());
+        assert!(is_binary(&mut binary).unwrap());
+        assert_eq!(9, binary.len());
+    }
+}
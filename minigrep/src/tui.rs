@@ -0,0 +1,444 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::config::Config;
+use crate::decompress;
+use crate::error::Error;
+use crate::matcher::Matcher;
+use crate::stream::{is_binary, search_reader};
+use crate::walk;
+
+/// At most this many lines are collected, so that editing the query stays
+/// responsive on large trees.
+const MAX_HITS: usize = 10_000;
+
+/// The same colors as the text output uses.
+const PATH_STYLE: Style = Style::new().fg(Color::Magenta);
+const NUMBER_STYLE: Style = Style::new().fg(Color::Green);
+const SEPARATOR_STYLE: Style = Style::new().fg(Color::Cyan);
+const MATCH_STYLE: Style = Style::new().fg(Color::Red).add_modifier(Modifier::BOLD);
+
+/// A selected line found by the browser.
+struct Hit {
+    path: PathBuf,
+    number: usize,
+    text: Vec<u8>,
+}
+
+/// What a key asks the browser to do beyond updating its own state.
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    /// The query changed, so the search has to be run again.
+    Search,
+    /// Open the selected line in the user's editor.
+    Open,
+    Quit,
+}
+
+/// The state of the browser: the query being edited, the lines it selects
+/// and which of them is selected in the list.
+struct App<'a> {
+    config: &'a Config,
+    query: String,
+    /// `None` while the query is empty or not valid.
+    matcher: Option<Matcher>,
+    hits: Vec<Hit>,
+    list: ListState,
+    /// How many hits the list shows at once, for Page Up and Page Down.
+    page: usize,
+    /// Shown instead of the key help, e.g. why the query is not valid.
+    message: Option<String>,
+    /// The lines of the file last previewed, so it is read once.
+    preview: Option<(PathBuf, Vec<String>)>,
+}
+
+/// Searches like `run` would, but shows the selected lines in a terminal UI
+/// to browse them in, with the lines around the selected one previewed. The
+/// query can be edited, and the selected line opened in `$EDITOR`. Returns
+/// whether the last query selected any line.
+pub(crate) fn browse(config: &Config) -> Result<bool, Error> {
+    if !io::stdout().is_terminal() {
+        return Err(Error::Usage("option '--tui' needs a terminal".to_string()));
+    }
+
+    let mut app = App::new(config);
+    let mut terminal = ratatui::try_init().map_err(Error::Output)?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result.map_err(Error::Output)?;
+    Ok(!app.hits.is_empty())
+}
+
+impl<'a> App<'a> {
+    fn new(config: &'a Config) -> Self {
+        let mut app = App {
+            config,
+            query: config.patterns.first().cloned().unwrap_or_default(),
+            matcher: None,
+            hits: Vec::new(),
+            list: ListState::default(),
+            page: 1,
+            message: None,
+            preview: None,
+        };
+        app.search();
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut stale = false;
+        loop {
+            // While keys are coming in, the query is taken as unfinished.
+            if stale && !event::poll(Duration::ZERO)? {
+                self.search();
+                stale = false;
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle_key(key) {
+                Action::None => {}
+                Action::Search => stale = true,
+                Action::Open => {
+                    ratatui::restore();
+                    let opened = self.open_selected();
+                    *terminal = ratatui::try_init()?;
+                    terminal.clear()?;
+                    if let Err(e) = opened {
+                        self.message = Some(format!("cannot run the editor: {e}"));
+                    }
+                }
+                Action::Quit => return Ok(()),
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let selected = self.list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if control => return Action::Quit,
+            KeyCode::Enter if !self.hits.is_empty() => return Action::Open,
+            KeyCode::Up => self.select(selected.saturating_sub(1)),
+            KeyCode::Char('p') if control => self.select(selected.saturating_sub(1)),
+            KeyCode::Down => self.select(selected + 1),
+            KeyCode::Char('n') if control => self.select(selected + 1),
+            KeyCode::PageUp => self.select(selected.saturating_sub(self.page)),
+            KeyCode::PageDown => self.select(selected + self.page),
+            KeyCode::Home => self.select(0),
+            KeyCode::End => self.select(usize::MAX),
+            KeyCode::Char('u') if control => {
+                self.query.clear();
+                return Action::Search;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                return Action::Search;
+            }
+            KeyCode::Char(c) if !control => {
+                self.query.push(c);
+                return Action::Search;
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Selects the hit at `index`, or the last one if there are fewer.
+    fn select(&mut self, index: usize) {
+        if !self.hits.is_empty() {
+            self.list.select(Some(index.min(self.hits.len() - 1)));
+        }
+    }
+
+    /// Searches for the current query and selects the first line found.
+    fn search(&mut self) {
+        self.hits.clear();
+        self.message = None;
+        self.matcher = None;
+        if !self.query.is_empty() {
+            let config = self.config;
+            let queries = slice::from_ref(&self.query);
+            match Matcher::new(queries, config.ignore_case, config.syntax(), false, config.anchor()) {
+                Ok(matcher) => {
+                    self.hits = find_hits(config, &matcher);
+                    self.matcher = Some(matcher);
+                }
+                Err(e) => self.message = Some(e.to_string()),
+            }
+        }
+        self.list.select(if self.hits.is_empty() { None } else { Some(0) });
+    }
+
+    /// Runs the editor named by `$VISUAL` or `$EDITOR` (or else `vi`) on the
+    /// file of the selected hit, at its line.
+    fn open_selected(&self) -> io::Result<()> {
+        let Some(hit) = self.list.selected().and_then(|selected| self.hits.get(selected)) else { return Ok(()) };
+        let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+        // The editor may come with arguments, as in "code --wait".
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let status = process::Command::new(program)
+            .args(words)
+            .arg(format!("+{}", hit.number))
+            .arg(&hit.path)
+            .status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(io::Error::other(format!("{program} exited with {status}"))),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input, list, preview, status] =
+            Layout::vertical([Constraint::Length(1), Constraint::Percentage(50), Constraint::Min(3), Constraint::Length(1)])
+                .areas(frame.area());
+
+        let prompt = Line::from(vec![Span::styled("> ", SEPARATOR_STYLE), Span::raw(&self.query)]);
+        frame.set_cursor_position((input.x + prompt.width() as u16, input.y));
+        frame.render_widget(Paragraph::new(prompt), input);
+
+        let show_names = Path::new(&self.config.file_path).is_dir();
+        let items: Vec<ListItem> = self.hits.iter().map(|hit| ListItem::new(self.hit_line(hit, show_names))).collect();
+        let hits = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(hits, list, &mut self.list);
+        self.page = usize::from(list.height).max(1);
+
+        let selected = self.list.selected().and_then(|selected| self.hits.get(selected));
+        if let Some(hit) = selected {
+            // Long paths are cut off at the right, so the title starts where
+            // the paths of the hits differ: below the searched directory, or
+            // at the name of the searched file.
+            let root = Path::new(&self.config.file_path);
+            let title = match hit.path.strip_prefix(root) {
+                Ok(relative) if !relative.as_os_str().is_empty() => relative,
+                _ => hit.path.file_name().map_or(hit.path.as_path(), Path::new),
+            };
+            let block = Block::bordered().title(Span::styled(title.display().to_string(), PATH_STYLE));
+            let height = usize::from(block.inner(preview).height);
+            let (path, number) = (hit.path.clone(), hit.number);
+            let lines = self.preview_lines(&path, number, height);
+            frame.render_widget(Paragraph::new(lines).block(block), preview);
+        } else {
+            frame.render_widget(Block::bordered(), preview);
+        }
+
+        let help = match self.hits.len() {
+            MAX_HITS => format!("first {MAX_HITS} lines"),
+            1 => "1 line".to_string(),
+            n => format!("{n} lines"),
+        };
+        let help = format!("{help} - Up/Down: select, Enter: open in $EDITOR, Esc: quit");
+        let status_line = match &self.message {
+            Some(message) => Span::styled(message.as_str(), MATCH_STYLE),
+            None => Span::raw(help).dim(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+
+    /// A hit as listed: its location, then the line with its matches highlighted.
+    fn hit_line(&self, hit: &Hit, show_names: bool) -> Line<'static> {
+        let mut spans = Vec::new();
+        if show_names {
+            spans.push(Span::styled(hit.path.display().to_string(), PATH_STYLE));
+            spans.push(Span::styled(":", SEPARATOR_STYLE));
+        }
+        spans.push(Span::styled(hit.number.to_string(), NUMBER_STYLE));
+        spans.push(Span::styled(":", SEPARATOR_STYLE));
+        spans.extend(self.highlighted(&hit.text));
+        Line::from(spans)
+    }
+
+    /// `text` in spans, with what the query matches in it highlighted.
+    fn highlighted(&self, text: &[u8]) -> Vec<Span<'static>> {
+        let matches = match (&self.matcher, self.config.invert_match) {
+            (Some(matcher), false) => matcher.find_iter(text),
+            _ => Vec::new(),
+        };
+        let mut spans = Vec::new();
+        let mut at = 0;
+        for span in matches {
+            spans.push(Span::raw(String::from_utf8_lossy(&text[at..span.start]).into_owned()));
+            spans.push(Span::styled(String::from_utf8_lossy(&text[span.clone()]).into_owned(), MATCH_STYLE));
+            at = span.end;
+        }
+        spans.push(Span::raw(String::from_utf8_lossy(&text[at..]).into_owned()));
+        spans
+    }
+
+    /// The lines of `path` around line `number` that fit in `height` rows,
+    /// numbered, with line `number` itself highlighted.
+    fn preview_lines(&mut self, path: &Path, number: usize, height: usize) -> Vec<Line<'static>> {
+        if self.preview.as_ref().is_none_or(|(previewed, _)| previewed != path) {
+            let lines = read_lines(self.config, path).unwrap_or_else(|e| vec![format!("cannot preview: {e}")]);
+            self.preview = Some((path.to_path_buf(), lines));
+        }
+        let Some((_, lines)) = &self.preview else { return Vec::new() };
+
+        let window = preview_window(number, lines.len(), height);
+        let width = window.end.to_string().len();
+        window
+            .map(|index| {
+                let gutter = Span::styled(format!("{:>width$} ", index + 1), NUMBER_STYLE);
+                let mut line = Line::from(gutter);
+                if index + 1 == number {
+                    line.spans.extend(self.highlighted(lines[index].as_bytes()));
+                    line.style(Style::new().add_modifier(Modifier::BOLD))
+                } else {
+                    line.spans.push(Span::raw(lines[index].clone()));
+                    line
+                }
+            })
+            .collect()
+    }
+}
+
+/// Finds up to [`MAX_HITS`] lines matching `matcher` in the files `config`
+/// names, skipping the ones that cannot be read, and binary files unless
+/// `--text` is given.
+fn find_hits(config: &Config, matcher: &Matcher) -> Vec<Hit> {
+    let root = Path::new(&config.file_path);
+    let files = match root.is_dir() {
+        true => walk::collect_files(root, config).unwrap_or_default().files,
+        false => vec![root.to_path_buf()],
+    };
+
+    let mut hits = Vec::new();
+    for path in files {
+        let Ok(reader) = open(config, &path) else { continue };
+        let found = search_reader(reader, |line| matcher.is_match(line) != config.invert_match)
+            .take(config.max_count.unwrap_or(usize::MAX));
+        for (number, text) in found.map_while(Result::ok) {
+            if hits.len() == MAX_HITS {
+                return hits;
+            }
+            hits.push(Hit { path: path.clone(), number, text });
+        }
+    }
+    hits
+}
+
+/// Opens `path` to be searched or previewed, decompressed with `-z`. Binary
+/// files are refused unless `--text` is given.
+fn open(config: &Config, path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut reader: Box<dyn BufRead> = match config.search_zip {
+        true => decompress::decompressed(reader)?,
+        false => Box::new(reader),
+    };
+    if !config.text && is_binary(&mut reader)? {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file"));
+    }
+    Ok(reader)
+}
+
+fn read_lines(config: &Config, path: &Path) -> io::Result<Vec<String>> {
+    let mut contents = Vec::new();
+    open(config, path)?.read_to_end(&mut contents)?;
+    Ok(String::from_utf8_lossy(&contents).lines().map(String::from).collect())
+}
+
+/// The (0-based) lines of a file of `len` lines to show in `height` rows so
+/// that line `number` (1-based) is in the middle, or as close as the start
+/// and end of the file allow.
+fn preview_window(number: usize, len: usize, height: usize) -> Range<usize> {
+    let start = number.saturating_sub(1 + height / 2).min(len.saturating_sub(height));
+    start..(start + height).min(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    /// The rows of `app` drawn on a 50 by 12 terminal, without trailing spaces.
+    fn screen(app: &mut App) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(50, 12)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(50)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn editing_the_query_and_moving_the_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pond.txt"), "a frog\na bog\nfrogs\n").unwrap();
+
+        let config = test_config(&["minigrep", "--tui", "og", dir.path().to_str().unwrap()]);
+        let mut app = App::new(&config);
+        assert_eq!(3, app.hits.len());
+        assert!(screen(&mut app)[7].starts_with("┌pond.txt─"));
+        assert_eq!(Action::None, app.handle_key(key(KeyCode::Down)));
+        assert_eq!(Action::None, app.handle_key(key(KeyCode::End)));
+        assert_eq!(Some(2), app.list.selected());
+        assert_eq!(Action::None, app.handle_key(key(KeyCode::Down)));
+        assert_eq!(Some(2), app.list.selected());
+
+        assert_eq!(Action::Search, app.handle_key(key(KeyCode::Char('s'))));
+        app.search();
+        assert_eq!(("ogs", Some(0)), (app.query.as_str(), app.list.selected()));
+        assert_eq!(vec![3], app.hits.iter().map(|hit| hit.number).collect::<Vec<_>>());
+        assert_eq!(Action::Open, app.handle_key(key(KeyCode::Enter)));
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        app.search();
+        assert!(app.hits.is_empty() && app.list.selected().is_none());
+        assert_eq!(Action::None, app.handle_key(key(KeyCode::Enter)));
+        assert_eq!(Action::Quit, app.handle_key(key(KeyCode::Esc)));
+    }
+
+    #[test]
+    fn draws_hits_and_a_preview_of_the_selected_one() {
+        let poem = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../poem.txt");
+        let config = test_config(&["minigrep", "--tui", "-i", "how", poem.to_str().unwrap()]);
+        let mut app = App::new(&config);
+        app.handle_key(key(KeyCode::Down));
+
+        let screen = screen(&mut app);
+
+        assert_eq!("> how", screen[0]);
+        assert_eq!("6:How dreary to be somebody!", screen[1]);
+        assert_eq!("7:How public, like a frog", screen[2]);
+        assert!(screen[7].starts_with("┌poem.txt─"), "{}", screen[7]);
+        assert_eq!("│6 How dreary to be somebody!", screen[8].trim_end_matches([' ', '│']));
+        assert_eq!("│7 How public, like a frog", screen[9].trim_end_matches([' ', '│']));
+        assert!(screen[11].starts_with("2 lines"));
+    }
+
+    #[test]
+    fn preview_is_centered_on_the_line_where_it_can() {
+        assert_eq!(2..7, preview_window(5, 100, 5));
+        assert_eq!(0..5, preview_window(1, 100, 5));
+        assert_eq!(95..100, preview_window(100, 100, 5));
+        assert_eq!(0..3, preview_window(2, 3, 5));
+    }
+}
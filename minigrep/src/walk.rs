@@ -0,0 +1,163 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+
+use crate::config::Config;
+
+/// The files found below a directory, and the places below it that could
+/// not be looked at, such as directories that cannot be read.
+#[derive(Default)]
+pub(crate) struct Walked {
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// Collects the files below `root` that a search should look at, sorted by
+/// path so that output is stable between runs.
+///
+/// Unless the command line says otherwise, files matched by `.gitignore` (or
+/// `.ignore`) and hidden files and directories are left out. `--include` and
+/// `--exclude` take gitignore-style globs relative to `root`; `--include`
+/// only narrows down the files the rest would find. Symbolic links
+/// are not followed. Parts of the tree that cannot be read are passed on to
+/// the caller rather than stopping the walk.
+pub(crate) fn collect_files(root: &Path, config: &Config) -> io::Result<Walked> {
+    // Whitelisted globs would win over .gitignore and the hidden filter, so
+    // --include is checked on the files found instead of while walking.
+    let mut include = OverrideBuilder::new(root);
+    for glob in &config.include {
+        include.add(glob).map_err(invalid_glob)?;
+    }
+    let include = include.build().map_err(invalid_glob)?;
+    let mut overrides = OverrideBuilder::new(root);
+    for glob in &config.exclude {
+        overrides.add(&format!("!{glob}")).map_err(invalid_glob)?;
+    }
+    let overrides = overrides.build().map_err(invalid_glob)?;
+
+    let walker = WalkBuilder::new(root)
+        .standard_filters(!config.no_ignore)
+        .hidden(!config.hidden)
+        // A .gitignore is honoured even when the tree is not a git checkout.
+        .require_git(false)
+        .overrides(overrides)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+
+    let mut walked = Walked { files: Vec::new(), errors: Vec::new() };
+    for entry in walker {
+        match entry {
+            Ok(entry) if entry.file_type().is_some_and(|file_type| file_type.is_file()) => {
+                if include.is_empty() || include.matched(entry.path(), false).is_whitelist() {
+                    walked.files.push(entry.into_path());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => walked.errors.push(walk_error(root, e)),
+        }
+    }
+
+    Ok(walked)
+}
+
+/// Splits an error of the walk into the path it is about, or `root` if it
+/// names none, and the error itself.
+fn walk_error(root: &Path, e: ignore::Error) -> (PathBuf, io::Error) {
+    match e {
+        ignore::Error::WithDepth { err, .. } => walk_error(root, *err),
+        ignore::Error::WithPath { path, err } => (path, walk_error(root, *err).1),
+        ignore::Error::Io(e) => (root.to_path_buf(), e),
+        e => (root.to_path_buf(), io::Error::other(e)),
+    }
+}
+
+fn invalid_glob(e: ignore::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn tree() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("b/nested")).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("b/nested/c.txt"), "").unwrap();
+        fs::write(root.join("a.rs"), "").unwrap();
+        fs::write(root.join(".hidden/d.txt"), "").unwrap();
+        fs::write(root.join("target/debug/e.rs"), "").unwrap();
+        fs::write(root.join("ignored.log"), "").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        dir
+    }
+
+    fn files(root: &Path, flags: &[&str]) -> Vec<PathBuf> {
+        let args = [&["minigrep"], flags, &["frog"]].concat();
+        collect_files(root, &test_config(&args)).unwrap().files
+    }
+
+    #[test]
+    fn skips_ignored_and_hidden_files_in_order() {
+        let dir = tree();
+        let root = dir.path();
+        let found = files(root, &[]);
+        let everything = files(root, &["--hidden", "--no-ignore"]);
+
+        assert_eq!(
+            vec![root.join("a.rs"), root.join("b/nested/c.txt"), root.join("target/debug/e.rs")],
+            found
+        );
+        assert_eq!(
+            vec![
+                root.join(".gitignore"),
+                root.join(".hidden/d.txt"),
+                root.join("a.rs"),
+                root.join("b/nested/c.txt"),
+                root.join("ignored.log"),
+                root.join("target/debug/e.rs"),
+            ],
+            everything
+        );
+    }
+
+    #[test]
+    fn include_and_exclude_globs() {
+        let dir = tree();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "*.log\ngen.rs\n").unwrap();
+        fs::write(root.join("gen.rs"), "").unwrap();
+        fs::write(root.join(".secret.rs"), "").unwrap();
+        let rust = files(root, &["--include", "*.rs"]);
+        let all_rust = files(root, &["--include", "*.rs", "--hidden", "--no-ignore"]);
+        let no_target = files(root, &["--exclude=target/**"]);
+        let both = files(root, &["--include=*.rs", "--exclude", "target"]);
+
+        // --include does not bring back ignored or hidden files.
+        assert_eq!(vec![root.join("a.rs"), root.join("target/debug/e.rs")], rust);
+        assert_eq!(
+            vec![root.join(".secret.rs"), root.join("a.rs"), root.join("gen.rs"), root.join("target/debug/e.rs")],
+            all_rust
+        );
+        assert_eq!(vec![root.join("a.rs"), root.join("b/nested/c.txt")], no_target);
+        assert_eq!(vec![root.join("a.rs")], both);
+    }
+
+    #[test]
+    fn unreadable_parts_are_passed_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("missing");
+        let walked = collect_files(&root, &test_config(&["minigrep", "frog"])).unwrap();
+
+        assert!(walked.files.is_empty());
+        let [(path, e)] = &walked.errors[..] else { panic!("expected one error, got {:?}", walked.errors) };
+        assert_eq!((&root, io::ErrorKind::NotFound), (path, e.kind()));
+    }
+}
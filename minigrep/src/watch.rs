@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use memchr::{memchr_iter, memrchr};
+
+use crate::config::Config;
+use crate::error::{Error, SearchError};
+use crate::printer::Printer;
+use crate::stream::{is_binary, search_reader_with_context, Line};
+use crate::walk;
+
+/// How long to wait between looking at the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What is known about a watched file from the last time it was read.
+#[derive(Default)]
+struct Watched {
+    /// Device and inode on Unix, to notice a file replaced by another one.
+    identity: Option<(u64, u64)>,
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Where the first line not yet read to its end starts.
+    offset: u64,
+    /// The number of lines before `offset`.
+    lines: usize,
+    /// Whether the file has been searched since it was last (re)written, so
+    /// that only what is appended to it is left to search.
+    searched: bool,
+    binary: bool,
+}
+
+/// Searches `config.file_path` like a normal search, then keeps polling it
+/// until the output is closed or an error stops it.
+///
+/// Like `tail -f`, lines appended to a file are searched as they arrive and
+/// numbered as part of the whole file. A file that is truncated, replaced
+/// (as editors do when saving) or rewritten in place is searched again from
+/// the start. Below a directory, new files are picked up and removed ones
+/// forgotten. A file is first searched whole, as by a normal search. After
+/// that, a last line without a newline may still be being written, so it is
+/// only searched (again, if it was there on the first pass) once its newline
+/// arrives. Binary files are not watched unless `--text` is given.
+pub(crate) fn watch(
+    config: &Config,
+    is_match: &dyn Fn(&[u8]) -> bool,
+    printer: &mut Printer<impl Write>,
+) -> Result<(), Error> {
+    let root = Path::new(&config.file_path);
+    let mut watched: BTreeMap<PathBuf, Watched> = BTreeMap::new();
+    let mut first = true;
+
+    loop {
+        let paths = match root.is_dir() {
+            true => {
+                printer.show_names = true;
+                let walked =
+                    walk::collect_files(root, config).map_err(|source| Error::Io { path: root.to_path_buf(), source })?;
+                for (path, e) in walked.errors {
+                    printer.skip_file(&path, SearchError::Read(e)).map_err(Error::Output)?;
+                }
+                walked.files
+            }
+            false => vec![root.to_path_buf()],
+        };
+        watched.retain(|path, _| paths.contains(path));
+
+        for path in paths {
+            let state = watched.entry(path.clone()).or_default();
+            match poll(config, is_match, printer, &path, state) {
+                Ok(()) => {}
+                // A file being saved can be missing for a moment; it is read
+                // from the start when it is back.
+                Err(SearchError::Read(e)) if !first && e.kind() == io::ErrorKind::NotFound => {
+                    *state = Watched::default();
+                }
+                Err(e) if path == root && first => return Err(e.at(&path)),
+                Err(e) => printer.skip_file(&path, e).map_err(Error::Output)?,
+            }
+        }
+
+        printer.flush().map_err(Error::Output)?;
+        first = false;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Searches whatever is new in the file at `path` since `state` was taken,
+/// or the whole file if it has been rewritten, and updates `state`.
+fn poll(
+    config: &Config,
+    is_match: &dyn Fn(&[u8]) -> bool,
+    printer: &mut Printer<impl Write>,
+    path: &Path,
+    state: &mut Watched,
+) -> Result<(), SearchError> {
+    let metadata = fs::metadata(path).map_err(SearchError::Read)?;
+    let replaced = identity(&metadata) != state.identity;
+    let truncated = metadata.len() < state.offset;
+    let rewritten = metadata.len() == state.len && metadata.modified().ok() != state.modified;
+    if replaced || truncated || rewritten {
+        *state = Watched { identity: identity(&metadata), ..Watched::default() };
+    } else if metadata.len() == state.len {
+        return Ok(());
+    }
+    if state.binary {
+        return Ok(());
+    }
+
+    let mut file = File::open(path).map_err(SearchError::Read)?;
+    file.seek(SeekFrom::Start(state.offset)).map_err(SearchError::Read)?;
+    let mut reader = BufReader::new(file);
+    if state.offset == 0 && !config.text && is_binary(&mut reader).map_err(SearchError::Read)? {
+        state.binary = true;
+        if path == Path::new(&config.file_path) {
+            eprintln!("minigrep: {}: binary file not watched, use --text to force", path.display());
+        }
+        return Ok(());
+    }
+
+    let (lines, offset) = (state.lines, state.offset);
+    let mut reader = CompleteLines::new(reader, state.searched);
+    let found = search_reader_with_context(&mut reader, is_match, config.before_context, config.after_context)
+        .map(|line| {
+            line.map(|line| match line {
+                Line::Match { number, offset: at, text } => {
+                    Line::Match { number: lines + number, offset: offset + at, text }
+                }
+                Line::Context { number, offset: at, text } => {
+                    Line::Context { number: lines + number, offset: offset + at, text }
+                }
+                Line::Separator => Line::Separator,
+            })
+        });
+    printer.print_file(path, found)?;
+
+    // Taken after reading, so a write in between shows up as a change next time.
+    let metadata = reader.inner.get_ref().metadata().map_err(SearchError::Read)?;
+    state.len = state.offset + reader.read;
+    state.modified = metadata.modified().ok();
+    state.lines += reader.lines;
+    state.offset += reader.complete;
+    state.searched = true;
+    Ok(())
+}
+
+/// Reads the lines of `inner`, with `hold_back` leaving out a last line that
+/// has no newline yet, and counts what was read and what was passed on.
+struct CompleteLines<R> {
+    inner: R,
+    hold_back: bool,
+    buffer: Vec<u8>,
+    /// What is left to pass on of `buffer`, up to just past its last newline
+    /// (or to its end, for a last line passed on without one).
+    start: usize,
+    end: usize,
+    /// Bytes read from `inner`, including a last line held back.
+    read: u64,
+    /// Bytes passed on, and the lines and bytes among them that ended with a newline.
+    consumed: u64,
+    lines: usize,
+    complete: u64,
+}
+
+impl<R: Read> CompleteLines<R> {
+    fn new(inner: R, hold_back: bool) -> Self {
+        CompleteLines {
+            inner,
+            hold_back,
+            buffer: Vec::new(),
+            start: 0,
+            end: 0,
+            read: 0,
+            consumed: 0,
+            lines: 0,
+            complete: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for CompleteLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for CompleteLines<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.start == self.end {
+            // Whatever is left has no newline; read on in case one follows.
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            self.end = 0;
+            let held = self.buffer.len();
+            self.buffer.resize(held + 8 * 1024, 0);
+            let read = match self.inner.read(&mut self.buffer[held..]) {
+                Ok(read) => read,
+                Err(e) => {
+                    self.buffer.truncate(held);
+                    return Err(e);
+                }
+            };
+            self.buffer.truncate(held + read);
+            if read == 0 && (self.hold_back || held == 0) {
+                return Ok(&[]);
+            }
+            if read == 0 {
+                self.end = held;
+                break;
+            }
+            self.read += read as u64;
+            self.end = memrchr(b'\n', &self.buffer[held..]).map_or(0, |last| held + last + 1);
+        }
+        Ok(&self.buffer[self.start..self.end])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        let consumed = &self.buffer[self.start..self.start + amount];
+        self.lines += memchr_iter(b'\n', consumed).count();
+        if let Some(last) = memrchr(b'\n', consumed) {
+            self.complete = self.consumed + last as u64 + 1;
+        }
+        self.consumed += amount as u64;
+        self.start += amount;
+    }
+}
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::matcher::Matcher;
+
+    #[test]
+    fn appended_lines_and_rewrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pond.txt");
+        let config = test_config(&["minigrep", "-n", "--watch", "frog", path.to_str().unwrap()]);
+        let matcher = Matcher::new(&config.patterns, false, config.syntax(), false, config.anchor()).unwrap();
+        let is_match = |line: &[u8]| matcher.is_match(line);
+
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out, false);
+        let mut state = Watched::default();
+        let mut step = |contents: Option<&str>, append: &str| {
+            if let Some(contents) = contents {
+                // A new file, as an editor saving it would make.
+                fs::remove_file(&path).ok();
+                fs::write(&path, contents).unwrap();
+            }
+            fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(append.as_bytes()).unwrap();
+            poll(&config, &is_match, &mut printer, &path, &mut state).unwrap();
+        };
+
+        step(Some("a frog\nbog\n"), "");
+        step(None, "frog\nfrog");
+        step(None, "gy and toad\n");
+        step(None, "");
+        step(Some("frog\n"), "");
+
+        assert_eq!("1:a frog\n3:frog\n4:froggy and toad\n1:frog\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn first_pass_searches_a_last_line_without_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pond.txt");
+        fs::write(&path, "a frog\nlast frog").unwrap();
+        let config = test_config(&["minigrep", "-n", "--watch", "frog", path.to_str().unwrap()]);
+        let matcher = Matcher::new(&config.patterns, false, config.syntax(), false, config.anchor()).unwrap();
+        let is_match = |line: &[u8]| matcher.is_match(line);
+
+        let mut out = Vec::new();
+        let mut printer = Printer::new(&config, &matcher, &mut out, false);
+        let mut state = Watched::default();
+        poll(&config, &is_match, &mut printer, &path, &mut state).unwrap();
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b" and toad\nfrog").unwrap();
+        poll(&config, &is_match, &mut printer, &path, &mut state).unwrap();
+
+        // The completed line is searched again; the new partial one waits for its newline.
+        assert_eq!("1:a frog\n2:last frog\n2:last frog and toad\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn complete_lines_hold_back_the_last_one() {
+        let mut contents = vec![b'x'; 20_000];
+        contents.extend_from_slice(b"\nfrog\nfro");
+        let mut reader = CompleteLines::new(&contents[..], true);
+        let mut passed = Vec::new();
+        reader.read_to_end(&mut passed).unwrap();
+
+        assert_eq!(&contents[..20_006], &passed[..]);
+        assert_eq!((20_009, 20_006, 20_006, 2), (reader.read, reader.consumed, reader.complete, reader.lines));
+
+        let mut reader = CompleteLines::new(&contents[..], false);
+        let mut passed = Vec::new();
+        reader.read_to_end(&mut passed).unwrap();
+
+        assert_eq!(contents, passed);
+        assert_eq!((20_009, 20_009, 20_006, 2), (reader.read, reader.consumed, reader.complete, reader.lines));
+    }
+
+    #[test]
+    fn complete_lines_stay_at_the_end() {
+        let mut reader = CompleteLines::new(&b"frog\nfrogs and a longer partial line"[..], true);
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line).unwrap();
+
+        assert_eq!(b"frog\n", &line[..]);
+        assert!(reader.fill_buf().unwrap().is_empty());
+        assert!(reader.fill_buf().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,443 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn poem() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../poem.txt")
+}
+
+/// Runs the binary with `args`, without inheriting `IGNORE_CASE` or a user
+/// configuration file from the environment the tests are run in.
+fn minigrep(args: &[&str], ignore_case: bool) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_minigrep"));
+    command.args(args).env_remove("IGNORE_CASE").env_remove("XDG_CONFIG_HOME").env_remove("HOME");
+    if ignore_case {
+        command.env("IGNORE_CASE", "1");
+    }
+    command.output().expect("failed to run minigrep")
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn case_sensitive_by_default() {
+    let output = minigrep(&["to", poem().to_str().unwrap()], false);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Are you nobody, too?\nHow dreary to be somebody!\n", stdout(&output));
+    assert_eq!("", stderr(&output));
+}
+
+#[test]
+fn ignore_case_from_environment() {
+    let output = minigrep(&["to", poem().to_str().unwrap()], true);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "Are you nobody, too?\nHow dreary to be somebody!\nTo tell your name the livelong day\nTo an admiring bog!\n",
+        stdout(&output)
+    );
+    assert_eq!("", stderr(&output));
+}
+
+#[test]
+fn flag_overrides_environment() {
+    let output = minigrep(&["--no-ignore-case", "To", poem().to_str().unwrap()], true);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("To tell your name the livelong day\nTo an admiring bog!\n", stdout(&output));
+}
+
+#[test]
+fn whole_word_and_whole_line() {
+    let words = minigrep(&["-wi", "to", poem().to_str().unwrap()], false);
+    let lines = minigrep(&["-x", "To an admiring bog!", poem().to_str().unwrap()], false);
+
+    assert_eq!(
+        "How dreary to be somebody!\nTo tell your name the livelong day\nTo an admiring bog!\n",
+        stdout(&words)
+    );
+    assert_eq!("To an admiring bog!\n", stdout(&lines));
+}
+
+#[test]
+fn fuzzy_matches_typos() {
+    let output = minigrep(&["--fuzzy=1", "-n", "admring", poem().to_str().unwrap()], false);
+
+    assert_eq!("9:To an admiring bog!\n", stdout(&output));
+}
+
+#[test]
+fn fuzzy_matches_are_ranked_within_a_file() {
+    let output = minigrep(&["--fuzzy=1", "-n", "bog", poem().to_str().unwrap()], false);
+    let context = minigrep(&["--fuzzy=1", "-n", "-A1", "bog", poem().to_str().unwrap()], false);
+
+    assert_eq!(
+        "9:To an admiring bog!\n1:I'm nobody! Who are you?\n2:Are you nobody, too?\n6:How dreary to be somebody!\n7:How public, like a frog\n",
+        stdout(&output)
+    );
+    // Context lines stay next to their matches, so the order is kept.
+    assert!(stdout(&context).starts_with("1:I'm nobody! Who are you?\n2:Are you nobody, too?\n3-Then"));
+}
+
+#[test]
+fn configuration_files_are_layered() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("home/minigrep")).unwrap();
+    std::fs::create_dir_all(dir.join("project/src")).unwrap();
+    std::fs::write(dir.join("home/minigrep/config.toml"), "ignore-case = true\nline-number = true\n").unwrap();
+    std::fs::write(dir.join("project/.minigreprc"), "line-number = false\n").unwrap();
+    std::fs::write(dir.join("project/src/poem.txt"), "a Frog\nfrog\n").unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .env_remove("IGNORE_CASE")
+            .env("XDG_CONFIG_HOME", dir.join("home"))
+            .current_dir(dir.join("project/src"))
+            .output()
+            .unwrap()
+    };
+    let layered = run(&["frog", "poem.txt"]);
+    let command_line = run(&["-n", "--no-ignore-case", "frog", "poem.txt"]);
+    let no_config = run(&["--no-config", "frog", "poem.txt"]);
+
+    assert_eq!("a Frog\nfrog\n", stdout(&layered));
+    assert_eq!("2:frog\n", stdout(&command_line));
+    assert_eq!("frog\n", stdout(&no_config));
+}
+
+#[test]
+fn broken_configuration_file() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(temp.path().join("minigrep")).unwrap();
+    std::fs::write(temp.path().join("minigrep/config.toml"), "colr = \"always\"\n").unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .env_remove("IGNORE_CASE")
+            .env("XDG_CONFIG_HOME", temp.path())
+            .output()
+            .unwrap()
+    };
+    let help = run(&["--help"]);
+    let version = run(&["-V"]);
+    let search = run(&["frog", poem().to_str().unwrap()]);
+
+    assert_eq!(Some(0), help.status.code());
+    assert!(stdout(&help).starts_with("Usage: minigrep"), "{}", stdout(&help));
+    assert_eq!(Some(0), version.status.code());
+    assert_eq!(Some(2), search.status.code());
+    assert!(stderr(&search).starts_with("Application error  : "), "{}", stderr(&search));
+    assert!(stderr(&search).contains("config.toml: unknown setting 'colr'"), "{}", stderr(&search));
+    assert!(!stderr(&search).contains("--help"), "{}", stderr(&search));
+}
+
+#[test]
+fn no_matches_prints_nothing() {
+    let output = minigrep(&["monomorphization", poem().to_str().unwrap()], false);
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!("", stderr(&output));
+}
+
+#[test]
+fn missing_arguments_is_a_usage_error() {
+    let output = minigrep(&[], false);
+
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!(
+        "Problem parsing arguments: missing QUERY\nTry 'minigrep --help' for more information.\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn missing_file_is_an_application_error() {
+    let output = minigrep(&["frog", "no-such-poem.txt"], false);
+
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert!(stderr(&output).starts_with("Application error  : no-such-poem.txt: "));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn failing_to_write_is_not_blamed_on_the_input() {
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["nobody", poem().to_str().unwrap()])
+        .env_remove("IGNORE_CASE")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("HOME")
+        .stdout(std::fs::File::create("/dev/full").unwrap())
+        .output()
+        .expect("failed to run minigrep");
+
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr(&output).starts_with("Application error  : cannot write results: "), "{}", stderr(&output));
+}
+
+#[test]
+fn reads_stdin_without_file_or_with_dash() {
+    for args in [&["-n", "o"][..], &["-n", "o", "-"][..]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .env_remove("IGNORE_CASE")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run minigrep");
+        child.stdin.take().unwrap().write_all(b"frog\nbog\ntoad").unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert_eq!(Some(0), output.status.code());
+        assert_eq!("1:frog\n2:bog\n3:toad\n", stdout(&output));
+        assert_eq!("", stderr(&output));
+    }
+}
+
+#[test]
+fn context_lines_with_separators() {
+    let output = minigrep(&["-n", "-B1", "-A1", "you", poem().to_str().unwrap()], false);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "1:I'm nobody! Who are you?\n2:Are you nobody, too?\n3-Then there's a pair of us - don't tell!\n4:They'd banish us, you know.\n5-\n--\n\
+7-How public, like a frog\n8:To tell your name the livelong day\n9-To an admiring bog!\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn summary_modes() {
+    let poem = poem();
+    let poem = poem.to_str().unwrap();
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::write(dir.join("frog.txt"), "a frog\n").unwrap();
+    std::fs::write(dir.join("bog.txt"), "a bog\n").unwrap();
+
+    let count = minigrep(&["-c", "-m2", "you", poem], false);
+    let limited = minigrep(&["-n", "-m1", "-A1", "you", poem], false);
+    let with = minigrep(&["-l", "-j1", "frog", dir.to_str().unwrap()], false);
+    let without = minigrep(&["-L", "-j1", "frog", dir.to_str().unwrap()], false);
+    let quiet = minigrep(&["-q", "frog", poem], false);
+    let quiet_miss = minigrep(&["-q", "toad", poem], false);
+
+    assert_eq!("2\n", stdout(&count));
+    assert_eq!("1:I'm nobody! Who are you?\n", stdout(&limited));
+    assert_eq!(format!("{}\n", dir.join("frog.txt").display()), stdout(&with));
+    assert_eq!(format!("{}\n", dir.join("bog.txt").display()), stdout(&without));
+    assert_eq!((Some(0), ""), (quiet.status.code(), stdout(&quiet)));
+    assert_eq!((Some(1), ""), (quiet_miss.status.code(), stdout(&quiet_miss)));
+}
+
+#[test]
+fn color_always_highlights_matches() {
+    let output = minigrep(&["--color=always", "-n", "-i", "FROG", poem().to_str().unwrap()], false);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "\x1b[32m7\x1b[0m\x1b[36m:\x1b[0mHow public, like a \x1b[1;31mfrog\x1b[0m\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn color_auto_is_plain_when_not_a_terminal() {
+    let output = minigrep(&["frog", poem().to_str().unwrap()], false);
+
+    assert_eq!("How public, like a frog\n", stdout(&output));
+}
+
+#[test]
+fn patterns_from_options_and_file() {
+    let temp = tempfile::tempdir().unwrap();
+    let queries = temp.path().join("queries.txt");
+    std::fs::write(&queries, "frog\nbog\n").unwrap();
+
+    let output = minigrep(&["-e", "nobody", "-f", queries.to_str().unwrap(), poem().to_str().unwrap()], false);
+    let all = minigrep(&["--all", "-e", "How", "-e", "frog", poem().to_str().unwrap()], false);
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "I'm nobody! Who are you?\nAre you nobody, too?\nHow public, like a frog\nTo an admiring bog!\n",
+        stdout(&output)
+    );
+    assert_eq!("How public, like a frog\n", stdout(&all));
+}
+
+#[test]
+fn json_lines_output() {
+    let output = minigrep(&["--json", "frog", poem().to_str().unwrap()], false);
+    let records: Vec<&str> = stdout(&output).lines().collect();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(2, records.len());
+    assert!(records[0].contains(r#""type":"match""#));
+    assert!(records[0].contains(r#""submatches":[{"end":23,"match":"frog","start":19}]"#));
+    assert!(records[1].contains(r#""type":"summary""#));
+}
+
+#[test]
+fn parallel_search_keeps_file_order() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+    for dir in ["a", "b", "c"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+        for file in 0..5 {
+            std::fs::write(root.join(dir).join(format!("{file}.txt")), "frog\nbog\nfrog\n").unwrap();
+        }
+    }
+
+    let serial = minigrep(&["-j1", "-A1", "frog", root.to_str().unwrap()], false);
+    let parallel = minigrep(&["-j4", "-A1", "frog", root.to_str().unwrap()], false);
+
+    assert_eq!(Some(0), parallel.status.code());
+    assert_eq!(15 * 3 + 14, stdout(&parallel).lines().count());
+    assert_eq!(stdout(&serial), stdout(&parallel));
+}
+
+#[test]
+fn replace_preview_and_write() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "a frog\nin a bog\n").unwrap();
+    std::fs::write(dir.join("sub/b.txt"), "frog, frog\n").unwrap();
+    std::fs::write(dir.join("sub/c.txt"), "nothing\n").unwrap();
+
+    let preview = minigrep(&["--regex", "--replace=[$0]", "[fb]rog", dir.join("a.txt").to_str().unwrap()], false);
+    let unchanged = std::fs::read_to_string(dir.join("a.txt")).unwrap();
+    let write = minigrep(&["-j1", "--replace", "toad", "--write", "frog", dir.to_str().unwrap()], false);
+    let a = std::fs::read_to_string(dir.join("a.txt")).unwrap();
+    let b = std::fs::read_to_string(dir.join("sub/b.txt")).unwrap();
+
+    assert_eq!("a [frog]\n", stdout(&preview));
+    assert_eq!("a frog\nin a bog\n", unchanged);
+    assert_eq!(Some(0), write.status.code());
+    assert_eq!(
+        format!("{}: 1 replacement\n{}: 2 replacements\n", dir.join("a.txt").display(), dir.join("sub/b.txt").display()),
+        stdout(&write)
+    );
+    assert_eq!(("a toad\nin a bog\n", "toad, toad\n"), (a.as_str(), b.as_str()));
+}
+
+#[test]
+fn index_build_and_query() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a.txt"), "a frog\n").unwrap();
+    std::fs::write(dir.join("sub/b.txt"), "a bog\nFrog\n").unwrap();
+
+    let missing = minigrep(&["index", "query", "frog", dir.to_str().unwrap()], false);
+    let build = minigrep(&["index", "build", dir.to_str().unwrap()], false);
+    let query = minigrep(&["index", "query", "-i", "frog", dir.to_str().unwrap()], false);
+    let file = minigrep(&["index", "query", "frog", dir.join("a.txt").to_str().unwrap()], false);
+
+    assert_eq!(Some(2), missing.status.code());
+    assert!(stderr(&missing).contains(".minigrep-index: no index, run 'minigrep index build' first"));
+    assert_eq!(Some(0), build.status.code());
+    assert!(stdout(&build).starts_with("Indexed 2 files"));
+    assert_eq!(
+        format!("{}:1:a frog\n{}:2:Frog\n", dir.join("a.txt").display(), dir.join("sub/b.txt").display()),
+        stdout(&query)
+    );
+    assert_eq!(Some(2), file.status.code());
+    assert!(stderr(&file).ends_with("a.txt: not a directory\n"), "{}", stderr(&file));
+}
+
+#[test]
+fn index_query_filters_like_a_search() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+    for file in ["a.txt", "notes.md", ".hidden/c.txt", "frog.log"] {
+        std::fs::write(dir.join(file), "a frog\n").unwrap();
+    }
+    std::fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    let root = dir.to_str().unwrap();
+
+    let build = minigrep(&["index", "build", "--hidden", "--no-ignore", root], false);
+    let plain = minigrep(&["index", "query", "-l", "frog", root], false);
+    let include = minigrep(&["index", "query", "-l", "--include", "*.txt", "frog", root], false);
+    let everything =
+        minigrep(&["index", "query", "-l", "--hidden", "--no-ignore", "--exclude=*.md", "frog", root], false);
+
+    let listed = |files: &[&str]| -> String {
+        files.iter().map(|file| format!("{}\n", dir.join(file).display())).collect()
+    };
+    assert!(stdout(&build).starts_with("Indexed 5 files"));
+    assert_eq!(listed(&["a.txt", "notes.md"]), stdout(&plain));
+    assert_eq!(listed(&["a.txt"]), stdout(&include));
+    assert_eq!(listed(&[".hidden/c.txt", "a.txt", "frog.log"]), stdout(&everything));
+}
+
+#[test]
+fn compressed_files_with_search_zip() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(b"a bog\na frog\n").unwrap();
+    std::fs::write(dir.join("app.log.1"), gzip.finish().unwrap()).unwrap();
+    std::fs::write(dir.join("app.log.2.zst"), zstd::encode_all(&b"frog\n"[..], 0).unwrap()).unwrap();
+
+    let explicit = minigrep(&["-zn", "frog", dir.join("app.log.1").to_str().unwrap()], false);
+    let walked = minigrep(&["-z", "-j1", "frog", dir.to_str().unwrap()], false);
+    let raw = minigrep(&["-j1", "frog", dir.to_str().unwrap()], false);
+
+    assert_eq!("2:a frog\n", stdout(&explicit));
+    assert_eq!(
+        format!("{}:2:a frog\n{}:1:frog\n", dir.join("app.log.1").display(), dir.join("app.log.2.zst").display()),
+        stdout(&walked)
+    );
+    assert_eq!(Some(1), raw.status.code());
+}
+
+#[test]
+fn mapped_and_read_files_agree() {
+    let poem = poem();
+    let args = ["-n", "-C1", "-i", "to", poem.to_str().unwrap()];
+    let read = minigrep(&[&["--no-mmap"][..], &args].concat(), false);
+    let mapped = minigrep(&[&["--mmap"][..], &args].concat(), false);
+    let from_pipe = minigrep(&["--mmap", "-n", "frog", "/dev/stdin"], false);
+
+    assert_eq!(Some(0), mapped.status.code());
+    assert_eq!(stdout(&read), stdout(&mapped));
+    // Standard input is /dev/null here, a device, so it is read instead.
+    assert_eq!(Some(1), from_pipe.status.code());
+}
+
+#[test]
+fn invalid_utf8_and_binary_files() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let latin1 = dir.join("latin1.txt");
+    let binary = dir.join("frog.bin");
+    std::fs::write(&latin1, b"caf\xe9 frog\nbog\n").unwrap();
+    std::fs::write(&binary, b"\x7fELF\0\0frog\0\n").unwrap();
+
+    let text = minigrep(&["frog", latin1.to_str().unwrap()], false);
+    let reported = minigrep(&["frog", binary.to_str().unwrap()], false);
+    let forced = minigrep(&["--text", "frog", binary.to_str().unwrap()], false);
+    let walked = minigrep(&["frog", dir.to_str().unwrap()], false);
+
+    assert_eq!(Some(0), text.status.code());
+    assert_eq!(&b"caf\xe9 frog\n"[..], &text.stdout[..]);
+    assert_eq!(format!("Binary file {} matches\n", binary.display()), stdout(&reported));
+    assert_eq!(&b"\x7fELF\0\0frog\0\n"[..], &forced.stdout[..]);
+    assert!(String::from_utf8_lossy(&walked.stdout).ends_with(":1:caf\u{fffd} frog\n"));
+    assert!(!String::from_utf8_lossy(&walked.stdout).contains("frog.bin"));
+}